@@ -0,0 +1,6 @@
+pub mod requests;
+pub mod responses;
+pub mod routing;
+pub mod server;
+pub mod security;
+pub mod websocket;
@@ -1,13 +1,21 @@
-mod requests;
-mod responses;
-mod routing;
-mod server;
-
-use requests::HTTPRequest;
-use responses::HTTPResponse;
-use routing::Router;
-use server::HTTPServer;
+use http_server_from_scratch::requests::{HTTPRequest, Method};
+use http_server_from_scratch::responses::{Cookie, HTTPResponse, Json, SameSite, SseEvent};
+use http_server_from_scratch::routing::{
+    request_id, request_id_header, Cors, JwtAlgorithm, JwtAuth, JwtClaims, MiddlewareOutcome, MiddlewareStack, RateLimiter,
+    RequestId, Router, TrailingSlash,
+};
+use http_server_from_scratch::security::constant_time_eq;
+use http_server_from_scratch::server::{AccessLogEntry, HTTPServer};
+use http_server_from_scratch::websocket::WebSocket;
 use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Where the Unix-socket listener binds (see main)
+const UNIX_SOCKET_PATH: &str = "/tmp/http-server-from-scratch.sock";
 
 // ============================================
 // Data Structures
@@ -20,11 +28,26 @@ struct User {
     email: String,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+struct ListParams {
+    page: i32,
+    limit: i32,
+    sort: String,
+}
+
+impl Default for ListParams {
+    fn default() -> Self {
+        Self { page: 1, limit: 10, sort: "name".into() }
+    }
+}
+
 #[derive(Serialize, Debug)]
 struct UserListResponse {
     page: i32,
     limit: i32,
     sort: String,
+    tags: Vec<String>,
     users: Vec<User>,
     total: u32,
 }
@@ -44,24 +67,41 @@ struct StatusResponse {
     status: String,
 }
 
+#[derive(Serialize, Debug)]
+struct UploadedField {
+    name: String,
+    filename: Option<String>,
+    content_type: Option<String>,
+    size: usize,
+}
+
 #[derive(Serialize, Debug)]
 struct HealthResponse {
     status: String,
     version: String,
 }
 
+// Stashed on the request by admin_check for the handler to read
+#[derive(Debug)]
+struct AdminIdentity {
+    name: String,
+}
+
 // ============================================
 // SERVER-LEVEL MIDDLEWARE (Layer 1)
 // ============================================
 
 fn request_logger(req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
-    println!("🌐 [SERVER] {} {}", req.method, req.route);
+    let id = req.extension::<RequestId>().map(|RequestId(id)| id.as_str()).unwrap_or("-");
+    match req.remote_addr {
+        Some(addr) => println!("🌐 [SERVER] [{}] {} {} from {}", id, req.method, req.route, addr),
+        None => println!("🌐 [SERVER] [{}] {} {}", id, req.method, req.route),
+    }
     Ok(req)
 }
 
-fn global_cors(req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
-    println!("🔓 [SERVER] CORS check passed");
-    Ok(req)
+fn served_by(_req: &HTTPRequest, res: HTTPResponse) -> HTTPResponse {
+    res.with_header("X-Served-By", "http-server-from-scratch")
 }
 
 fn security_check(req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
@@ -69,12 +109,22 @@ fn security_check(req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
     Ok(req)
 }
 
+// Answers /robots.txt itself, so the request never reaches a router.
+// A successful short-circuit, so Respond rather than Err.
+fn robots_txt(req: HTTPRequest) -> MiddlewareOutcome {
+    if req.method == Method::Get && req.route == "/robots.txt" {
+        println!("🤖 [SERVER] Serving robots.txt from middleware");
+        return MiddlewareOutcome::Respond(HTTPResponse::ok("").with_text_body("User-agent: *\nDisallow: /api/\n"));
+    }
+    MiddlewareOutcome::Continue(req)
+}
+
 fn maintenance_mode(req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
     let maintenance = false; // Set to true to test
 
     if maintenance {
         println!("🚧 [SERVER] Maintenance mode active - blocking request");
-        Err(HTTPResponse::new(503, "Service under maintenance"))
+        Err(HTTPResponse::service_unavailable("Service under maintenance"))
     } else {
         Ok(req)
     }
@@ -84,39 +134,70 @@ fn maintenance_mode(req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
 // ROUTER-LEVEL MIDDLEWARE (Layer 2)
 // ============================================
 
+// Keys a client may send as X-API-Key
+const API_KEYS: [&str; 2] = ["mykey123", "partner-key-456"];
+
 fn api_key_check(req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
+    // Check every key (no short-circuit), each compared in constant time
+    let is_valid = |key: &str| API_KEYS.iter().fold(false, |found, valid| constant_time_eq(key, valid) | found);
+
     match req.get_header("X-API-Key") {
-        Some(key) if !key.is_empty() => {
+        Some(key) if is_valid(&key) => {
             println!("🔑 [ROUTER] API key validated: {}", key);
             Ok(req)
         }
         _ => {
             println!("❌ [ROUTER] Missing or invalid API key");
-            Err(HTTPResponse::new(401, "API key required"))
+            Err(HTTPResponse::unauthorized("API key required"))
         }
     }
 }
 
+fn no_store(_req: &HTTPRequest, res: HTTPResponse) -> HTTPResponse {
+    res.with_header("Cache-Control", "no-store")
+}
+
 // ============================================
 // ROUTE-LEVEL MIDDLEWARE (Layer 3)
 // ============================================
 
+// Admins authenticate with an HS256 token signed with this secret (demo only,
+// a real app would load it from config)
+static ADMIN_JWT: LazyLock<JwtAuth> = LazyLock::new(|| JwtAuth::new("supersecret").algorithm(JwtAlgorithm::HS256));
+
 fn admin_check(req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
-    match req.get_header("X-Admin-Key") {
-        Some(key) if key == "supersecret" => {
-            println!("👑 [ROUTE] Admin access granted");
+    // 401 for a missing, bad or expired token...
+    let mut req = ADMIN_JWT.check(req).inspect_err(|_| println!("⛔ [ROUTE] Admin token missing or invalid"))?;
+
+    // ...403 for a valid token without the admin role
+    let claims = req.extension::<JwtClaims>().cloned();
+    match claims {
+        Some(claims) if claims.get_str("role") == Some("admin") => {
+            // Hand the verified identity to the handler
+            let name = claims.subject().unwrap_or("admin").to_string();
+            println!("👑 [ROUTE] Admin access granted to {}", name);
+            req.insert_extension(AdminIdentity { name });
             Ok(req)
         }
         _ => {
             println!("⛔ [ROUTE] Admin access denied");
-            Err(HTTPResponse::new(403, "Admin access required"))
+            Err(HTTPResponse::forbidden("Admin access required"))
         }
     }
 }
 
+// Route-level middleware is a plain fn, so the limiter it uses lives in a static
+static ADMIN_LIMITER: LazyLock<RateLimiter> = LazyLock::new(|| RateLimiter::new(60, Duration::from_secs(60)));
+
 fn rate_limit(req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
-    println!("⏱️  [ROUTE] Rate limit check passed");
-    Ok(req)
+    // 60 requests a minute per client IP
+    let client = req.remote_addr.map(|addr| addr.ip().to_string()).unwrap_or_default();
+    let result = ADMIN_LIMITER.check(req);
+    match &result {
+        Ok(_) => println!("⏱️  [ROUTE] Rate limit check passed for {}", client),
+        Err(_) => println!("⛔ [ROUTE] Rate limit exceeded for {}", client),
+    }
+    result
 }
 
 // ============================================
@@ -134,7 +215,7 @@ fn home(_req: HTTPRequest) -> HTTPResponse {
                 <li><a href="/">/ - Public (server middleware only)</a></li>
                 <li><a href="/about">/about - About page</a></li>
                 <li><a href="/api/users">/api/users - Needs API key (server + router)</a></li>
-                <li><a href="/api/admin">/api/admin - Needs API key + Admin key (all layers)</a></li>
+                <li><a href="/api/admin">/api/admin - Needs API key + admin token (all layers)</a></li>
             </ul>
             "#
         )
@@ -158,17 +239,275 @@ fn about(_req: HTTPRequest) -> HTTPResponse {
         )
 }
 
+fn old_about(_req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Redirecting to /about");
+    HTTPResponse::redirect_permanent("/about")
+}
+
+fn raw_bytes(_req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Serving raw bytes");
+    HTTPResponse::from_bytes(200, vec![0xFF, 0x00, 0xFE, 0x0A])
+}
+
+fn echo_bytes(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Echoing {} body bytes", req.body_bytes().len());
+    HTTPResponse::from_bytes(200, req.body_bytes().to_vec())
+}
+
+// Newline-delimited JSON, parsed one value at a time instead of into a Vec
+fn count_items(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Counting JSON items");
+
+    let items = serde_json::Deserializer::from_reader(req.body_reader()).into_iter::<serde_json::Value>();
+    let mut count = 0;
+    for item in items {
+        if let Err(e) = item {
+            return HTTPResponse::bad_request(&format!("Invalid JSON: {}", e));
+        }
+        count += 1;
+    }
+    HTTPResponse::json_value(200, serde_json::json!({ "count": count }))
+}
+
+fn upload(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Receiving upload");
+
+    match req.multipart() {
+        Ok(fields) => {
+            let response: Vec<UploadedField> = fields
+                .into_iter()
+                .map(|field| UploadedField {
+                    size: field.data.len(),
+                    name: field.name,
+                    filename: field.filename,
+                    content_type: field.content_type,
+                })
+                .collect();
+
+            HTTPResponse::ok_json(response).unwrap()
+        }
+        Err(e) => {
+            println!("   Multipart parse error: {}", e);
+            HTTPResponse::bad_request(&format!("Invalid multipart body: {}", e))
+        }
+    }
+}
+
+fn show_cookies(req: HTTPRequest) -> Json<HashMap<String, String>> {
+    println!("✅ [HANDLER] Echoing cookies");
+    Json(req.cookies())
+}
+
+fn set_preferences(_req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Setting preference cookies");
+
+    HTTPResponse::ok("Preferences saved")
+        .with_cookie("theme", "dark")
+        .with_set_cookie(
+            Cookie::new("sid", "abc123")
+                .path("/")
+                .max_age(3600)
+                .http_only()
+                .same_site(SameSite::Lax)
+        )
+}
+
+fn repeated_headers(_req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Sending repeated headers");
+
+    HTTPResponse::ok("Check the X-Test headers")
+        .with_header("X-Test", "a")
+        .with_header("X-Test", "b")
+}
+
+fn whoami(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Reporting client address");
+
+    match req.remote_addr {
+        Some(addr) => HTTPResponse::ok(&addr.ip().to_string()),
+        None => HTTPResponse::ok("unknown"),
+    }
+}
+
+// Same greeting as HTML, JSON or plain text, whichever the client prefers
+fn greeting(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Negotiating greeting format");
+
+    let response = match req.preferred_content_type(&["text/html", "application/json", "text/plain"]).as_deref() {
+        Some("text/html") => HTTPResponse::ok("").with_html_body("<h1>Hello!</h1>"),
+        Some("application/json") => HTTPResponse::json_value(200, serde_json::json!({ "greeting": "Hello!" })),
+        Some(_) => HTTPResponse::ok("").with_text_body("Hello!"),
+        None => HTTPResponse::new(406, "Not Acceptable"),
+    };
+    response.vary("Accept")
+}
+
+fn host_info(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Reporting requested host");
+
+    HTTPResponse::json_value(200, serde_json::json!({
+        "host": req.host(),
+        "hostname": req.host_without_port(),
+    }))
+}
+
+// Registered for GET and POST: providers verify with GET, then deliver with POST
+fn webhook(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Webhook received via {}", req.method);
+    HTTPResponse::ok(&format!("Webhook {} received", req.method))
+}
+
+fn echo_method(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Echoing method {}", req.method);
+    HTTPResponse::ok(req.method_str())
+}
+
+fn bearer_echo(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Checking bearer token");
+
+    match req.bearer_token() {
+        Some(token) => HTTPResponse::ok(&token),
+        None => HTTPResponse::unauthorized("Bearer token required")
+            .with_header("WWW-Authenticate", "Bearer"),
+    }
+}
+
+fn basic_echo(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Checking basic auth");
+
+    match req.basic_auth() {
+        Some((username, _password)) => HTTPResponse::ok(&username),
+        None => HTTPResponse::unauthorized("Basic credentials required")
+            .with_header("WWW-Authenticate", "Basic realm=\"demo\""),
+    }
+}
+
+fn echo_query(req: HTTPRequest) -> Json<HashMap<String, Vec<String>>> {
+    println!("✅ [HANDLER] Echoing query params");
+    Json(req.query_params)
+}
+
+fn order_by_id(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Looking up order by id");
+    HTTPResponse::ok(&format!("Order #{}", req.param("id", "")))
+}
+
+fn order_by_name(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Looking up order by name");
+    HTTPResponse::ok(&format!("Order named {}", req.param("name", "")))
+}
+
+fn profile(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Showing a profile");
+    HTTPResponse::ok(&format!("Profile of {}", req.param("name", "")))
+}
+
+fn my_profile(_req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Showing your profile");
+    HTTPResponse::ok("Your profile")
+}
+
+// Each named status constructor, for checking status lines
+fn status_example(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Status example");
+
+    match req.param("kind", "").as_str() {
+        "no-content" => HTTPResponse::no_content(),
+        "created" => HTTPResponse::created("Created"),
+        "bad-request" => HTTPResponse::bad_request("Bad input"),
+        "unauthorized" => HTTPResponse::unauthorized("Log in first"),
+        "forbidden" => HTTPResponse::forbidden("Not yours"),
+        "internal-error" => HTTPResponse::internal_error("Something broke"),
+        "service-unavailable" => HTTPResponse::service_unavailable("Back soon"),
+        // Any numeric code, e.g. /status/418
+        kind => match kind.parse::<u16>() {
+            Ok(code) => HTTPResponse::new(code, ""),
+            Err(_) => HTTPResponse::not_found("Unknown status example"),
+        },
+    }
+}
+
+fn plain_text(_req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Sending plain text");
+    HTTPResponse::ok("").with_text_body("hi")
+}
+
+fn stream_lines(_req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Streaming chunked response");
+
+    let (sender, receiver) = tokio::sync::mpsc::channel(8);
+    tokio::spawn(async move {
+        for line in ["first chunk\n", "second chunk\n"] {
+            if sender.send(line.as_bytes().to_vec()).await.is_err() {
+                break; // Client went away
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    });
+
+    HTTPResponse::chunked(receiver)
+}
+
+// Server-Sent Events: try it with `curl -N` or an EventSource in the browser
+fn live_ticks(_req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Streaming server-sent events");
+
+    let (sender, receiver) = tokio::sync::mpsc::channel(8);
+    tokio::spawn(async move {
+        for tick in 1..=3 {
+            let event = SseEvent::data(&format!("{{\"tick\":{}}}", tick)).event("tick").id(&tick.to_string());
+            if sender.send(event).await.is_err() {
+                break; // Client went away
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    });
+
+    HTTPResponse::event_stream(receiver)
+}
+
+// Async handler: awaits without blocking the worker thread
+async fn slow(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Waiting before responding");
+
+    let delay_ms = req.query_int("ms", 100).clamp(0, 2000) as u64;
+    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+    HTTPResponse::ok(&format!("Waited {}ms", delay_ms))
+}
+
+// Sends every message straight back until the client closes
+async fn echo_socket(_req: HTTPRequest, mut socket: WebSocket) {
+    println!("✅ [HANDLER] WebSocket connected");
+
+    while let Ok(Some(message)) = socket.recv().await {
+        if socket.send(message).await.is_err() {
+            break;
+        }
+    }
+    println!("✅ [HANDLER] WebSocket closed");
+}
+
+fn get_file(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Looking up file");
+
+    let path = req.param("path", "");
+    HTTPResponse::ok(&path)
+}
+
 fn list_users(req: HTTPRequest) -> HTTPResponse {
     println!("✅ [HANDLER] Listing users");
 
-    let page = req.query_int("page", 1);
-    let limit = req.query_int("limit", 10);
-    let sort = req.query("sort", "name");
+    let params = match req.query_params_as::<ListParams>() {
+        Ok(params) => params,
+        Err(e) => return HTTPResponse::bad_request(&e),
+    };
+    let tags = req.query_all("tag");
 
     let response = UserListResponse {
-        page,
-        limit,
-        sort,
+        page: params.page,
+        limit: params.limit,
+        sort: params.sort,
+        tags,
         users: vec![
             User { id: 1, name: "Alice".into(), email: "alice@example.com".into() },
             User { id: 2, name: "Bob".into(), email: "bob@example.com".into() },
@@ -183,6 +522,10 @@ fn get_user(req: HTTPRequest) -> HTTPResponse {
     println!("✅ [HANDLER] Getting user");
 
     let user_id = req.param("id", "0");
+    if user_id == "0" {
+        return HTTPResponse::not_found("User 0 doesn't exist");
+    }
+
     let include_posts = req.query_bool("include_posts", false);
     let include_comments = req.query_bool("include_comments", false);
 
@@ -194,7 +537,9 @@ fn get_user(req: HTTPRequest) -> HTTPResponse {
         include_comments,
     };
 
+    // Lets clients revalidate with If-None-Match and get a 304
     HTTPResponse::ok_json(response).unwrap()
+        .with_etag(&format!("user-{}-v1", user_id))
 }
 
 fn create_user(req: HTTPRequest) -> HTTPResponse {
@@ -215,32 +560,39 @@ fn create_user(req: HTTPRequest) -> HTTPResponse {
         }
         Err(e) => {
             println!("   JSON parse error: {}", e);
-            HTTPResponse::new(400, &format!("Invalid JSON: {}", e))
+            HTTPResponse::error_json(400, &format!("Invalid JSON: {}", e))
         }
     }
 }
 
-fn update_user(req: HTTPRequest) -> HTTPResponse {
+// Bad ids and bodies turn into 400s through `?`
+fn update_user(req: HTTPRequest) -> Result<Json<StatusResponse>, HTTPResponse> {
     println!("✅ [HANDLER] Updating user");
 
-    let user_id = req.param("id", "0");
+    let user_id = req.param_parse::<u32>("id")?;
+    let user = req.body::<User>()?;
+    println!("   Updating user {}: {:?}", user_id, user);
 
-    match req.body::<User>() {
-        Ok(user) => {
-            println!("   Updating user {}: {:?}", user_id, user);
+    Ok(Json(StatusResponse {
+        id: user_id,
+        status: "updated".into(),
+    }))
+}
 
-            let response = StatusResponse {
-                id: user_id.parse().unwrap_or(0),
-                status: "updated".into(),
-            };
+// Partial update: fields missing from the body keep their current values
+fn patch_user(req: HTTPRequest) -> Result<Json<User>, HTTPResponse> {
+    println!("✅ [HANDLER] Patching user");
 
-            HTTPResponse::ok_json(response).unwrap()
-        }
-        Err(e) => {
-            println!("   JSON parse error: {}", e);
-            HTTPResponse::new(400, &format!("Invalid JSON: {}", e))
-        }
-    }
+    let user_id = req.param_parse::<u32>("id")?;
+    let mut user = User {
+        id: user_id,
+        name: format!("User {}", user_id),
+        email: format!("user{}@example.com", user_id),
+    };
+    req.body_merge_into(&mut user)?;
+    println!("   Patched user {}: {:?}", user_id, user);
+
+    Ok(Json(user))
 }
 
 fn delete_user(req: HTTPRequest) -> HTTPResponse {
@@ -249,35 +601,95 @@ fn delete_user(req: HTTPRequest) -> HTTPResponse {
     let user_id = req.param("id", "0");
     println!("   Deleted user {}", user_id);
 
-    HTTPResponse::new(204, "")
+    HTTPResponse::no_content()
 }
 
-fn health_check(_req: HTTPRequest) -> HTTPResponse {
+fn health_check(_req: HTTPRequest) -> Json<HealthResponse> {
     println!("✅ [HANDLER] Health check");
 
-    let response = HealthResponse {
+    Json(HealthResponse {
         status: "healthy".into(),
         version: "1.0.0".into(),
-    };
+    })
+}
 
-    HTTPResponse::ok_json(response).unwrap()
+// Dynamic JSON: no struct, fields added at runtime
+fn ping(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Ping");
+
+    let mut body = serde_json::json!({ "ok": true });
+    if req.has_query("msg") {
+        body["msg"] = req.query("msg", "").into();
+    }
+    HTTPResponse::json_value(200, body)
+}
+
+// Access log: print each finished request and keep a copy in access.log
+fn log_access(entry: &AccessLogEntry) {
+    let line = format!("{} {} {} {}B {}ms", entry.method, entry.path, entry.status, entry.body_size, entry.elapsed.as_millis());
+    println!("📝 [ACCESS] {}", line);
+
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("access.log") {
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+// Server fallback: branded page for paths no router claims
+// Same branding for the server's own errors (405s, panics); status and Allow are kept
+fn branded_error_page(code: u16, message: &str) -> HTTPResponse {
+    HTTPResponse::ok("").with_html_body(&format!("<h1>{} - {}</h1>", code, message))
+}
+
+fn not_found_page(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Fallback 404 for {}", req.route);
+
+    HTTPResponse::not_found("").with_html_body(&format!(
+        "<h1>404 - Page Not Found</h1><p>Nothing lives at {}</p>",
+        req.route
+    ))
 }
 
-fn admin_dashboard(_req: HTTPRequest) -> HTTPResponse {
+// Router fallback: unknown v2 endpoints get a JSON error instead of plain text
+fn unknown_v2_endpoint(req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Unknown v2 endpoint {}", req.route);
+    HTTPResponse::error_json(404, &format!("Unknown v2 endpoint: {}", req.route))
+}
+
+fn health_check_v2(_req: HTTPRequest) -> Json<HealthResponse> {
+    println!("✅ [HANDLER] Health check (v2)");
+
+    Json(HealthResponse {
+        status: "healthy".into(),
+        version: "2.0.0".into(),
+    })
+}
+
+fn admin_dashboard(req: HTTPRequest) -> HTTPResponse {
     println!("✅ [HANDLER] Admin dashboard accessed");
 
+    // Set by admin_check
+    let admin_name = req.extension::<AdminIdentity>()
+        .map(|admin| admin.name.as_str())
+        .unwrap_or("admin");
+
     HTTPResponse::ok("")
-        .with_html_body(
+        .with_html_body(&format!(
             r#"
             <h1>👑 Admin Dashboard</h1>
-            <p>Welcome, admin! You passed all security layers.</p>
+            <p>Welcome, {}! You passed all security layers.</p>
             <ul>
                 <li>Total users: 100</li>
                 <li>Active sessions: 42</li>
                 <li>Requests today: 5,432</li>
             </ul>
-            "#
-        )
+            "#,
+            admin_name
+        ))
+}
+
+fn admin_stats(_req: HTTPRequest) -> HTTPResponse {
+    println!("✅ [HANDLER] Admin stats accessed");
+    HTTPResponse::ok("users=100 sessions=42")
 }
 
 // ============================================
@@ -292,12 +704,51 @@ async fn main() {
 
     // Public router - NO router middleware
     let public = Router::new("/")
+        .trailing_slash(TrailingSlash::MatchBoth)  // /about/ works too
         .get("/", home, vec![])
-        .get("/about", about, vec![]);  // ← Added!
+        .get("/about", about, vec![])  // ← Added!
+        .get("/about-us", old_about, vec![])
+        .get("/files/{*path}", get_file, vec![])
+        .get("/bytes", raw_bytes, vec![])
+        .post("/upload", upload, vec![])
+        .post("/echo-bytes", echo_bytes, vec![])
+        .post("/count-items", count_items, vec![])
+        .get("/cookies", show_cookies, vec![])
+        .post("/preferences", set_preferences, vec![])
+        .get("/headers", repeated_headers, vec![])
+        .get("/whoami", whoami, vec![])
+        .get("/text", plain_text, vec![])
+        .get("/greeting", greeting, vec![])
+        .get("/ping", ping, vec![])
+        .get("/host", host_info, vec![])
+        .route(&["GET", "POST"], "/webhook", webhook, vec![])
+        .any("/method", echo_method, vec![])
+        .get("/status/{kind}", status_example, vec![])
+        .get("/stream", stream_lines, vec![])
+        .get("/events", live_ticks, vec![])
+        .get_async("/slow", slow, vec![])
+        .ws("/ws/echo", echo_socket)
+        .get("/echo-query", echo_query, vec![])
+        .get(r"/orders/{id:\d+}", order_by_id, vec![])   // Numeric ids only...
+        .get("/orders/{name}", order_by_name, vec![])    // ...everything else lands here
+        .get("/profiles/{name}", profile, vec![])
+        .get("/profiles/me", my_profile, vec![])   // Static segment wins despite coming second
+        .get("/auth/bearer", bearer_echo, vec![])
+        .get("/auth/basic", basic_echo, vec![])
+        .static_dir("/static", "static");
+
+    // Admin sub-router - mounted under /api, so api_key_check runs before admin_check
+    let admin_v1 = Router::new("/v1/admin")
+        .add_middleware(admin_check)
+        .get("/stats", admin_stats, vec![]);
+
+    // Route-level checks shared by the protected routes
+    let protected = MiddlewareStack::new().then(admin_check).then(rate_limit);
 
     // API router - WITH router middleware
     let api = Router::new("/api")
         .add_middleware(api_key_check)  // Layer 2: Router-level
+        .add_response_middleware(no_store)  // API responses are never cached
 
         // All routes with proper middleware
         .get("/health", health_check, vec![])
@@ -305,42 +756,234 @@ async fn main() {
         .post("/users", create_user, vec![])  // ← Added!
         .get("/users/{id}", get_user, vec![])  // ← Added!
         .put("/users/{id}", update_user, vec![])  // ← Added!
-        .delete("/users/{id}", delete_user, vec![admin_check, rate_limit])  // ← Added!
-        .get("/admin", admin_dashboard, vec![admin_check, rate_limit]);
+        .patch("/users/{id}", patch_user, vec![])
+        .delete_with("/users/{id}", delete_user, &protected)  // ← Added!
+        .get_with("/admin", admin_dashboard, &protected)
+        .mount(admin_v1);
+
+    // API v2 router - shares the "/api" text prefix but is a separate mount
+    let api_v2 = Router::new("/apiv2")
+        .trailing_slash(TrailingSlash::Redirect)  // /apiv2/health/ → 301 /apiv2/health
+        .get("/health", health_check_v2, vec![])
+        .fallback(unknown_v2_endpoint);
+
+    // Guestbook router - closure handlers sharing app state
+    let entries: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+    let signed_entries = Arc::clone(&entries);
+    let guestbook = Router::new("/guestbook")
+        .post("/", move |req: HTTPRequest| {
+            let name = req.query("name", "anonymous");
+            println!("✅ [HANDLER] Signing guestbook as {}", name);
+            signed_entries.lock().unwrap().push(name);
+            HTTPResponse::created("Signed")
+        }, vec![])
+        .get("/", move |_req: HTTPRequest| {
+            println!("✅ [HANDLER] Reading guestbook");
+            HTTPResponse::ok_json(entries.lock().unwrap().clone()).unwrap()
+        }, vec![]);
+
+    // Virtual hosts: same "/" prefix as the public router, picked by Host header
+    let api_host = Router::new("/")
+        .get("/", |_req: HTTPRequest| HTTPResponse::ok("API host home"), vec![])
+        .get("/status", |_req: HTTPRequest| HTTPResponse::json_value(200, serde_json::json!({ "api": "up" })), vec![]);
+    let www_host = Router::new("/")
+        .get("/", |_req: HTTPRequest| HTTPResponse::ok("").with_html_body("<h1>WWW host home</h1>"), vec![]);
+
+    // Tight limit so it's easy to trip: 3 requests per 10 seconds per IP
+    let limited = Router::new("/limited")
+        .add_middleware(RateLimiter::new(3, Duration::from_secs(10)).middleware())
+        .get("/", |_req: HTTPRequest| {
+            println!("✅ [HANDLER] Serving rate-limited route");
+            HTTPResponse::ok("Within limit")
+        }, vec![]);
 
     println!("📋 Routes registered:");
     println!("  GET    /");
     println!("  GET    /about");
+    println!("  GET    /about-us  (301 → /about)");
+    println!("  GET    /files/{{*path}}");
+    println!("  GET    /bytes  (binary body)");
+    println!("  POST   /upload  (multipart/form-data)");
+    println!("  POST   /echo-bytes  (raw body echoed back)");
+    println!("  POST   /count-items  (newline-delimited JSON)");
+    println!("  GET    /cookies");
+    println!("  POST   /preferences  (sets cookies)");
+    println!("  GET    /headers  (repeated headers)");
+    println!("  GET    /whoami  (client IP)");
+    println!("  GET    /text  (plain text body)");
+    println!("  GET    /greeting  (HTML/JSON/text by Accept header)");
+    println!("  GET    /ping?msg=...  (ad-hoc JSON value)");
+    println!("  GET    /host  (Host header, with and without port)");
+    println!("  GET    /webhook  (same handler as POST)");
+    println!("  POST   /webhook");
+    println!("  *      /method  (any method, echoes it back)");
+    println!("  GET    /status/{{kind}}  (named status constructors)");
+    println!("  GET    /stream  (chunked transfer encoding)");
+    println!("  GET    /events  (server-sent events)");
+    println!("  GET    /slow?ms=100  (async handler)");
+    println!("  GET    /ws/echo  (WebSocket echo)");
+    println!("  GET    /echo-query  (query params as JSON)");
+    println!(r"  GET    /orders/{{id:\d+}}  (numeric ids)");
+    println!("  GET    /orders/{{name}}");
+    println!("  GET    /profiles/{{name}}");
+    println!("  GET    /profiles/me");
+    println!("  GET    /auth/bearer  (echoes bearer token)");
+    println!("  GET    /auth/basic  (echoes basic auth user)");
+    println!("  GET    /static/*  (files from ./static)");
     println!("  GET    /api/health");
     println!("  GET    /api/users");
     println!("  POST   /api/users");
     println!("  GET    /api/users/{{id}}");
     println!("  PUT    /api/users/{{id}}");
+    println!("  PATCH  /api/users/{{id}}  (JSON merge patch)");
     println!("  DELETE /api/users/{{id}}");
-    println!("  GET    /api/admin\n");
+    println!("  GET    /api/admin  (Bearer token with role=admin)");
+    println!("  GET    /api/v1/admin/stats  (mounted sub-router)");
+    println!("  GET    /apiv2/health");
+    println!("  *      /apiv2/...  (router fallback, JSON 404)");
+    println!("  GET    /guestbook");
+    println!("  POST   /guestbook?name=...");
+    println!("  GET    /limited  (3 requests / 10s per IP)");
+    println!("  GET    /robots.txt  (answered by server middleware)");
+    println!("  GET    / and /status  (Host: api.localhost)");
+    println!("  GET    /  (Host: www.localhost)");
+    println!("  GET    /healthz  (liveness probe, skips all middleware)");
+    println!("  *      anything else  (server fallback, HTML 404)\n");
 
     println!("📋 Middleware Layers:");
-    println!("  Layer 1 (Server):  request_logger → cors → security");
+    println!("  Layer 1 (Server):  request_id → request_logger → cors preflight → security → robots.txt (responses: cors, served_by, request_id)");
     println!("  Layer 2 (Router):  api_key_check (only on /api routes)");
     println!("  Layer 3 (Route):   admin_check + rate_limit (on protected routes)");
     println!("  Layer 4 (Handler): Your business logic\n");
 
-    println!("🌐 Server starting on http://127.0.0.1:8081\n");
+    // So the admin routes can be tried by hand: curl -H "Authorization: Bearer <token>" ...
+    let expires = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() + 3600;
+    let demo_token = ADMIN_JWT.encode(&serde_json::json!({ "sub": "admin", "role": "admin", "exp": expires })).unwrap();
+    println!("🔐 Admin token (valid 1h): {}\n", demo_token);
+
+    println!("🌐 Server starting on http://127.0.0.1:8081");
+    println!("🔐 HTTPS (public routes, self-signed cert) on https://127.0.0.1:8443");
+    println!("🔌 Unix socket (public routes) at {}", UNIX_SOCKET_PATH);
+    println!("🚦 One-connection-at-a-time server (public routes) on http://127.0.0.1:8082\n");
     println!("Run: ./src/test_server.sh to test all features!\n");
 
+    // HTTPS listener for the public routes, using the dev cert in ./certs
+    let https = HTTPServer::new("127.0.0.1:8443")
+        .add_middleware(request_logger)
+        .add_router(public.clone());
+    tokio::spawn(async move {
+        if let Err(e) = https.run_tls("certs/localhost.pem", "certs/localhost-key.pem").await {
+            eprintln!("HTTPS server failed: {}", e);
+        }
+    });
+
+    // Same public routes over a Unix socket, for a reverse proxy sitting in front
+    let unix = HTTPServer::new(UNIX_SOCKET_PATH)
+        .add_middleware(request_logger)
+        .add_router(public.clone());
+    let unix_server = tokio::spawn(async move {
+        let shutdown = async {
+            let _ = tokio::signal::ctrl_c().await;
+        };
+        if let Err(e) = unix.run_unix_until(UNIX_SOCKET_PATH, shutdown).await {
+            eprintln!("Unix socket server failed: {}", e);
+        }
+    });
+
+    // Single-slot listener to show max_connections: a second client waits
+    // until the first one disconnects
+    let single = HTTPServer::new("127.0.0.1:8082")
+        .add_middleware(request_logger)
+        .max_connections(1)
+        .add_router(public.clone());
+    tokio::spawn(async move {
+        if let Err(e) = single.run().await {
+            eprintln!("Single-connection server failed: {}", e);
+        }
+    });
+
+    // Browser clients on other origins may call the API with their key
+    let cors = Cors::new()
+        .allow_origin("*")
+        .allow_methods(["GET", "POST", "PUT", "DELETE"])
+        .allow_headers(["X-API-Key", "Content-Type"])
+        .max_age(600);
+
     // Start server with ALL FOUR LAYERS
-    HTTPServer::new("127.0.0.1:8081")
+    let server = HTTPServer::new("127.0.0.1:8081")
         // LAYER 1: Server-level middleware (runs on EVERY request)
+        .add_middleware(request_id)  // Tags the request first so later layers can log it
         .add_middleware(request_logger)
-        .add_middleware(global_cors)
+        .add_middleware(cors.preflight())  // Answers CORS preflights before auth runs
         .add_middleware(security_check)
         .add_middleware(maintenance_mode)
+        .add_middleware(robots_txt)
+
+        // Response middleware runs on the way out
+        .add_response_middleware(cors.headers())
+        .add_response_middleware(served_by)
+        .add_response_middleware(request_id_header)
+
+        // One line per request with status, size and latency
+        .access_log_with(log_access)
+
+        // Anything no router matches gets the branded 404 page
+        .fallback(not_found_page)
+        // ...and 405s and panics get the same look
+        .error_page(405..=405, branded_error_page)
+        .error_page(500..=599, branded_error_page)
+
+        // For load balancers: answered even in maintenance mode or without an API key
+        .health_endpoint("/healthz")
+
+        // Reject uploads over 1MB with 413
+        .max_body_size(1024 * 1024)
+        // ...and header blocks over 8KB with 431
+        .max_header_size(8 * 1024)
+        // Drop clients that stall mid-request with 408
+        .read_timeout(Duration::from_secs(3))
+        // On Ctrl-C, give in-flight requests 5s before closing their connections
+        .shutdown_timeout(Duration::from_secs(5))
+        // Gzip larger text responses for clients that accept it
+        .enable_compression()
+        .compressible_types(["text/*", "application/json", "application/javascript", "image/svg+xml"])
+        // Cap concurrent connections so a flood can't exhaust memory/FDs
+        .max_connections(256)
+        // ...and recycle keep-alive connections so no client holds a slot forever
+        .max_requests_per_connection(1000)
+        // Small API responses go out immediately, and dead peers get noticed
+        .tcp_nodelay(true)
+        .tcp_keepalive(Duration::from_secs(60))
 
         // Add routers (Layer 2, 3, 4 inside)
         .add_router(public)
         .add_router(api)
+        .add_router(api_v2)
+        .add_router(guestbook)
+        .add_router(limited)
+
+        // Host-specific routers win over the prefix-matched ones above
+        .add_router_for_host("api.localhost", api_host)
+        .add_router_for_host("www.localhost", www_host);
+
+    // DUMP_ROUTES=1 cargo run → what actually got registered, middleware in run order
+    if std::env::var_os("DUMP_ROUTES").is_some() {
+        println!("🔎 Route dump:");
+        for info in server.describe() {
+            println!("  {}", info);
+        }
+        println!();
+    }
 
-        .run()
+    // Ctrl-C stops accepting and lets in-flight requests finish
+    server
+        .run_until(async {
+            let _ = tokio::signal::ctrl_c().await;
+            println!("\n🛑 Shutting down...");
+        })
         .await
         .unwrap();
+
+    // Let the Unix listener finish so its socket file gets cleaned up
+    let _ = unix_server.await;
 }
\ No newline at end of file
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use serde::Serialize;
+use crate::requests::{normalize_path, HTTPRequest, Method};
+
+// Build a request without writing it out on the wire, e.g. to call a
+// middleware or handler directly in a test:
+//   let req = HTTPRequest::builder().method("POST").path("/users")
+//       .header("X-API-Key", "k").body_json(&user).build();
+// Defaults to GET / over HTTP/1.1 with no headers.
+#[derive(Debug, Clone)]
+pub struct RequestBuilder {
+    method: Method,
+    path: String,
+    version: String,
+    headers: HashMap<String, String>,
+    query: Vec<(String, String)>,
+    params: HashMap<String, String>,
+    body: Vec<u8>,
+    remote_addr: Option<SocketAddr>
+}
+
+impl HTTPRequest {
+    pub fn builder() -> RequestBuilder {
+        RequestBuilder::new()
+    }
+}
+
+impl Default for RequestBuilder {
+    fn default() -> Self {
+        Self {
+            method: Method::Get,
+            path: "/".to_string(),
+            version: "HTTP/1.1".to_string(),
+            headers: HashMap::new(),
+            query: Vec::new(),
+            params: HashMap::new(),
+            body: Vec::new(),
+            remote_addr: None
+        }
+    }
+}
+
+impl RequestBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // "GET", "post", Method::Put, ...
+    pub fn method(mut self, method: impl Into<Method>) -> Self {
+        self.method = method.into();
+        self
+    }
+
+    // May carry its own query string ("/items?page=2"); it's normalized like a parsed request's
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = version.to_string();
+        self
+    }
+
+    // Replaces any earlier value for `name`
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name.to_ascii_lowercase(), value.to_string());
+        self
+    }
+
+    // Appended to the query string (encoded), so repeating a key adds values
+    pub fn query(mut self, key: &str, value: &str) -> Self {
+        self.query.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    // A route param, as the router would fill in for "/users/{id}"
+    pub fn param(mut self, name: &str, value: &str) -> Self {
+        self.params.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    // Sets Content-Length to match
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self.headers.insert("content-length".to_string(), self.body.len().to_string());
+        self
+    }
+
+    // JSON body with Content-Type: application/json (unless one was set).
+    // Panics if `value` can't be serialized, e.g. a map with non-string keys.
+    pub fn body_json<T: Serialize + ?Sized>(self, value: &T) -> Self {
+        let json = serde_json::to_vec(value).expect("request body should serialize to JSON");
+        let mut builder = self.body(json);
+        builder.headers.entry("content-type".to_string()).or_insert_with(|| "application/json".to_string());
+        builder
+    }
+
+    pub fn remote_addr(mut self, addr: SocketAddr) -> Self {
+        self.remote_addr = Some(addr);
+        self
+    }
+
+    pub fn build(self) -> HTTPRequest {
+        let mut target = self.path;
+        if !self.query.is_empty() {
+            let encoded = serde_urlencoded::to_string(&self.query).unwrap_or_default();
+            target.push(if target.contains('?') { '&' } else { '?' });
+            target.push_str(&encoded);
+        }
+
+        let (path, query_params) = HTTPRequest::extract_query_params(&target);
+        let query_string = target.split_once('?').map(|(_, query)| query.to_string()).unwrap_or_default();
+
+        HTTPRequest {
+            method: self.method,
+            route: normalize_path(&path),
+            version: self.version,
+            headers: self.headers,
+            body: self.body,
+            route_params: self.params,
+            query_params,
+            query_string,
+            remote_addr: self.remote_addr,
+            extensions: HashMap::new()
+        }
+    }
+}
@@ -1,2 +1,8 @@
 pub mod request;
-pub use request::*;
\ No newline at end of file
+pub mod multipart;
+pub mod method;
+pub mod builder;
+pub use request::*;
+pub use multipart::*;
+pub use method::*;
+pub use builder::*;
\ No newline at end of file
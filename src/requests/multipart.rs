@@ -0,0 +1,108 @@
+use crate::requests::HTTPRequest;
+
+#[derive(Debug, Clone)]
+pub struct MultipartField {
+    pub name: String,
+    pub filename: Option<String>,   // ← Only set for file uploads
+    pub content_type: Option<String>,
+    pub data: Vec<u8>
+}
+
+impl MultipartField {
+    // Field value as text (for plain form fields)
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.data).into_owned()
+    }
+}
+
+impl HTTPRequest {
+    // Parse a multipart/form-data body into its parts
+    pub fn multipart(&self) -> Result<Vec<MultipartField>, String> {
+        let content_type = self.content_type()
+            .ok_or("Missing Content-Type header")?;
+        let boundary = extract_boundary(&content_type)
+            .ok_or("Content-Type is not multipart/form-data with a boundary")?;
+
+        let delimiter = format!("--{}", boundary).into_bytes();
+        let mut part_delimiter = b"\r\n".to_vec();
+        part_delimiter.extend_from_slice(&delimiter);
+
+        let body = &self.body;
+        let mut pos = find(body, &delimiter, 0)
+            .ok_or("Multipart body has no opening boundary")? + delimiter.len();
+        let mut fields = Vec::new();
+
+        loop {
+            // "--boundary--" closes the body
+            if body[pos..].starts_with(b"--") {
+                return Ok(fields);
+            }
+            if !body[pos..].starts_with(b"\r\n") {
+                return Err("Malformed multipart boundary line".to_string());
+            }
+            pos += 2;
+
+            let part_end = find(body, &part_delimiter, pos)
+                .ok_or("Multipart body is missing its closing boundary")?;
+            fields.push(parse_part(&body[pos..part_end])?);
+            pos = part_end + part_delimiter.len();
+        }
+    }
+}
+
+fn parse_part(part: &[u8]) -> Result<MultipartField, String> {
+    let header_end = find(part, b"\r\n\r\n", 0)
+        .ok_or("Multipart part has no header block")?;
+    let headers = String::from_utf8_lossy(&part[..header_end]);
+
+    let mut name = None;
+    let mut filename = None;
+    let mut content_type = None;
+
+    for line in headers.split("\r\n") {
+        let Some((header, value)) = line.split_once(':') else { continue };
+        let value = value.trim();
+
+        if header.trim().eq_ignore_ascii_case("Content-Disposition") {
+            for param in value.split(';').skip(1) {
+                if let Some((key, param_value)) = param.trim().split_once('=') {
+                    let param_value = param_value.trim_matches('"').to_string();
+                    match key.trim() {
+                        "name" => name = Some(param_value),
+                        "filename" => filename = Some(param_value),
+                        _ => {}
+                    }
+                }
+            }
+        } else if header.trim().eq_ignore_ascii_case("Content-Type") {
+            content_type = Some(value.to_string());
+        }
+    }
+
+    Ok(MultipartField {
+        name: name.ok_or("Multipart part is missing a field name")?,
+        filename,
+        content_type,
+        data: part[header_end + 4..].to_vec()
+    })
+}
+
+fn extract_boundary(content_type: &str) -> Option<String> {
+    let (mime, params) = content_type.split_once(';')?;
+    if !mime.trim().eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+
+    params.split(';')
+        .filter_map(|param| param.trim().split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim().trim_matches('"').to_string())
+        .filter(|boundary| !boundary.is_empty())
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack.get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|pos| pos + from)
+}
@@ -1,48 +1,158 @@
+use std::any::{Any, TypeId};
 use std::collections::HashMap;
-use serde::{Deserialize};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use crate::requests::Method;
 
 #[derive(Debug, Clone)]
 pub struct HTTPRequest {
-    pub method: String,      // ← Not Option!
+    pub method: Method,      // ← Not Option!
     pub route: String,
     pub version: String,
     pub headers: HashMap<String, String>,
-    body: String,
+    pub(crate) body: Vec<u8>,
     pub route_params: HashMap<String, String>,
-    pub query_params: HashMap<String, String>
+    pub query_params: HashMap<String, Vec<String>>,
+    pub query_string: String,   // ← Raw text after '?', still encoded
+    pub remote_addr: Option<SocketAddr>,  // ← Filled in by the server per connection
+    pub extensions: HashMap<TypeId, Arc<dyn Any + Send + Sync>>  // ← Typed values middleware hands to the handler
 }
 
 impl HTTPRequest {
     pub fn new(request: &str) -> Result<Self, String> {
-        let parts: Vec<&str> = request.split("\r\n\r\n").collect();
-        let body = parts.get(1).unwrap_or(&"").to_string();
+        Self::from_bytes(request.as_bytes())
+    }
+
+    // Parse raw request bytes; the body is kept as-is so binary uploads survive
+    pub fn from_bytes(request: &[u8]) -> Result<Self, String> {
+        let (head, body) = match request.windows(4).position(|window| window == b"\r\n\r\n") {
+            Some(pos) => (&request[..pos], request[pos + 4..].to_vec()),
+            None => (request, Vec::new()),
+        };
+        let head = String::from_utf8_lossy(head);
 
-        let (method, full_route, version) = Self::extract_method_route_and_version(request)?;
-        let headers_map = Self::extract_headers(request);
+        let (method, full_route, version) = Self::extract_method_route_and_version(&head)?;
+        let headers_map = Self::extract_headers(&head);
         let (path, query_params) = Self::extract_query_params(full_route.as_str());
+        let query_string = full_route.split_once('?').map(|(_, query)| query.to_string()).unwrap_or_default();
 
         Ok(Self {
             method,
-            route: path,
+            route: normalize_path(&path),
             version,
             headers: headers_map,
             body,
             route_params: HashMap::new(), // for injecting route params
-            query_params
+            query_params,
+            query_string,
+            remote_addr: None,
+            extensions: HashMap::new()
         })
     }
 
+    // Wire name of the method, e.g. "GET"
+    pub fn method_str(&self) -> &str {
+        self.method.as_str()
+    }
+
+    // Attach a value for later middleware/the handler, one per type
+    pub fn insert_extension<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.extensions.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    pub fn extension<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.extensions.get(&TypeId::of::<T>())?.downcast_ref::<T>()
+    }
+
+    // An empty (or whitespace-only) body is its own error rather than
+    // serde's "EOF while parsing", so handlers can tell clients what's wrong
     pub fn body<'a, T: Deserialize<'a>>(&'a self) -> Result<T, String> {
-        serde_json::from_str(&self.body)
+        if self.body_is_empty() {
+            return Err("Request body is empty".to_string());
+        }
+        serde_json::from_slice(&self.body)
             .map_err(|e| format!("Failed to deserialize request body: {}", e))
     }
 
-    // Get query param, returns owned String
+    // For endpoints where the body is optional: None when nothing was sent
+    pub fn body_opt<'a, T: Deserialize<'a>>(&'a self) -> Result<Option<T>, String> {
+        if self.body_is_empty() {
+            return Ok(None);
+        }
+        self.body().map(Some)
+    }
+
+    fn body_is_empty(&self) -> bool {
+        self.body.iter().all(u8::is_ascii_whitespace)
+    }
+
+    // Just the fields the client sent, for PATCH handlers that need to tell
+    // "left out" apart from "set to the default"
+    pub fn body_partial(&self) -> Result<Map<String, Value>, String> {
+        match self.body::<Value>()? {
+            Value::Object(fields) => Ok(fields),
+            _ => Err("Request body must be a JSON object".to_string()),
+        }
+    }
+
+    // Apply the body to `existing` as a JSON merge patch (RFC 7396): sent
+    // fields overwrite, nested objects merge, null clears, missing fields
+    // stay as they were. `existing` is untouched if the result doesn't fit T.
+    pub fn body_merge_into<T: Serialize + DeserializeOwned>(&self, existing: &mut T) -> Result<(), String> {
+        let patch = self.body_partial()?;
+        let mut merged = serde_json::to_value(&*existing)
+            .map_err(|e| format!("Failed to serialize current value: {}", e))?;
+        merge_patch(&mut merged, patch);
+
+        *existing = serde_json::from_value(merged)
+            .map_err(|e| format!("Failed to deserialize request body: {}", e))?;
+        Ok(())
+    }
+
+    // Raw body exactly as received (after chunked decoding), UTF-8 or not
+    pub fn body_bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    // Read the body without copying it, e.g. for serde_json::from_reader or
+    // a streaming Deserializer over a large array
+    pub fn body_reader(&self) -> impl std::io::Read + '_ {
+        self.body.as_slice()
+    }
+
+    // Deserialize the whole query string into a struct (first value of repeated
+    // params). Missing fields fall back to #[serde(default)].
+    pub fn query_params_as<T: DeserializeOwned>(&self) -> Result<T, String> {
+        let pairs: Vec<(&str, &str)> = self.query_params.iter()
+            .filter_map(|(key, values)| values.first().map(|value| (key.as_str(), value.as_str())))
+            .collect();
+
+        // Round-trip through urlencoding so serde_urlencoded can parse numbers/bools
+        let encoded = serde_urlencoded::to_string(pairs)
+            .map_err(|e| format!("Failed to encode query params: {}", e))?;
+        serde_urlencoded::from_str(&encoded)
+            .map_err(|e| format!("Invalid query params: {}", e))
+    }
+
+    // Get query param, returns owned String (first value if repeated)
     pub fn query(&self, key: &str, default: &str) -> String {
+        self.first_query(key)
+            .cloned()
+            .unwrap_or_else(|| default.to_string())
+    }
+
+    // Get every value of a repeated query param, in order
+    pub fn query_all(&self, key: &str) -> Vec<String> {
         self.query_params
             .get(key)
             .cloned()
-            .unwrap_or_else(|| default.to_string())
+            .unwrap_or_default()
     }
 
     // Get path param, returns owned String
@@ -53,18 +163,48 @@ impl HTTPRequest {
             .unwrap_or_else(|| default.to_string())
     }
 
+    // Get path param parsed into any FromStr type (missing is an error, not a default)
+    pub fn param_parse<T: FromStr>(&self, key: &str) -> Result<T, String> {
+        let value = self.route_params
+            .get(key)
+            .ok_or_else(|| format!("Missing path parameter '{}'", key))?;
+
+        value.parse()
+            .map_err(|_| format!("Invalid path parameter '{}': '{}'", key, value))
+    }
+
     // Get query param as i32
     pub fn query_int(&self, key: &str, default: i32) -> i32 {
-        self.query_params
-            .get(key)
+        self.first_query(key)
             .and_then(|v| v.parse().ok())
             .unwrap_or(default)
     }
 
     // Get query param as bool
     pub fn query_bool(&self, key: &str, default: bool) -> bool {
-        self.query_params
-            .get(key)
+        self.first_query(key)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    }
+
+    // Offsets, ids, timestamps. Negative values fall back to the default.
+    pub fn query_u64(&self, key: &str, default: u64) -> u64 {
+        self.query_parse(key, default)
+    }
+
+    // NaN and infinities fall back to the default too
+    pub fn query_f64(&self, key: &str, default: f64) -> f64 {
+        self.first_query(key)
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|v| v.is_finite())
+            .unwrap_or(default)
+    }
+
+    // Get query param as any FromStr type, e.g. req.query_parse::<u16>("port", 80).
+    // Missing, empty and unparseable values all give `default`.
+    pub fn query_parse<T: FromStr>(&self, key: &str, default: T) -> T {
+        self.first_query(key)
+            .filter(|v| !v.is_empty())
             .and_then(|v| v.parse().ok())
             .unwrap_or(default)
     }
@@ -74,59 +214,255 @@ impl HTTPRequest {
         self.query_params.contains_key(key)
     }
 
+    // Header names are case-insensitive, so they're stored lowercased
     pub fn get_header(&self, header: &str) -> Option<String> {
-        self.headers.get(header).cloned()
+        self.headers.get(&header.to_ascii_lowercase()).cloned()
     }
 
-    fn extract_query_params(full_route: &str)  -> (String, HashMap<String, String>) {
-        if let Some((path, query_params_str)) = full_route.split_once("?") {
-            let mut query_params = HashMap::new();
+    // Declared body size; None if missing or not a number
+    pub fn content_length(&self) -> Option<usize> {
+        self.get_header("Content-Length")?.trim().parse().ok()
+    }
 
-            for param in query_params_str.split("&") {
-                if let Some((param_name, param_value)) = param.split_once("=") {
-                    query_params.insert(param_name.to_string(), param_value.to_string());
+    // Content-Type as sent, parameters included, e.g. "text/html; charset=utf-8"
+    pub fn content_type(&self) -> Option<String> {
+        self.get_header("Content-Type")
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+
+    // (mime type, charset), both lowercased: "application/json; charset=UTF-8"
+    // gives ("application/json", Some("utf-8"))
+    pub fn content_type_mime(&self) -> Option<(String, Option<String>)> {
+        let content_type = self.content_type()?;
+        let mut parts = content_type.split(';');
+        let mime = parts.next()?.trim().to_ascii_lowercase();
+        if mime.is_empty() {
+            return None;
+        }
+
+        let charset = parts
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+            .map(|(_, value)| value.trim().trim_matches('"').to_ascii_lowercase());
+        Some((mime, charset))
+    }
+
+    // The Host header as sent, e.g. "example.com:8080"
+    pub fn host(&self) -> Option<String> {
+        self.get_header("Host")
+            .map(|host| host.trim().to_string())
+            .filter(|host| !host.is_empty())
+    }
+
+    // Host with any ":port" removed; IPv6 literals keep their brackets ("[::1]")
+    pub fn host_without_port(&self) -> Option<String> {
+        let host = self.host()?;
+        if host.starts_with('[') {
+            return host.find(']').map(|end| host[..=end].to_string());
+        }
+        match host.rsplit_once(':') {
+            Some((name, port)) if port.bytes().all(|byte| byte.is_ascii_digit()) => Some(name.to_string()),
+            _ => Some(host),
+        }
+    }
+
+    // Parse the Cookie header ("a=1; b=2"). On duplicate names the first wins.
+    pub fn cookies(&self) -> HashMap<String, String> {
+        let mut cookies = HashMap::new();
+        let Some(header) = self.get_header("Cookie") else {
+            return cookies;
+        };
+
+        for pair in header.split(';') {
+            if let Some((name, value)) = pair.split_once('=') {
+                let name = name.trim();
+                if !name.is_empty() {
+                    cookies.entry(name.to_string())
+                        .or_insert_with(|| value.trim().to_string());
                 }
             }
+        }
 
-            (path.to_string(), query_params)
+        cookies
+    }
+
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        self.cookies().remove(name)
+    }
+
+    // Token from "Authorization: Bearer <token>" (scheme is case-insensitive)
+    pub fn bearer_token(&self) -> Option<String> {
+        self.authorization_credentials("Bearer")
+            .filter(|token| !token.is_empty())
+    }
+
+    // (username, password) from "Authorization: Basic <base64 user:pass>"
+    pub fn basic_auth(&self) -> Option<(String, String)> {
+        let credentials = self.authorization_credentials("Basic")?;
+        let decoded = BASE64.decode(credentials).ok()?;
+        let decoded = String::from_utf8(decoded).ok()?;
+        let (username, password) = decoded.split_once(':')?;
+        Some((username.to_string(), password.to_string()))
+    }
+
+    // The part after "<scheme> " in the Authorization header, trimmed
+    fn authorization_credentials(&self, scheme: &str) -> Option<String> {
+        let header = self.get_header("Authorization")?;
+        let (given_scheme, credentials) = header.trim().split_once(' ')?;
+        if !given_scheme.eq_ignore_ascii_case(scheme) {
+            return None;
+        }
+        Some(credentials.trim().to_string())
+    }
+
+    // True if the Accept header allows `mime` (no Accept header allows anything)
+    pub fn accepts(&self, mime: &str) -> bool {
+        match self.headers.get("accept") {
+            Some(accept) => accept_quality(accept, mime) > 0.0,
+            None => true,
+        }
+    }
+
+    // The entry of `offered` the client likes best, by Accept q-values.
+    // Ties go to the earlier entry; no Accept header picks the first one.
+    // None means nothing offered is acceptable (answer with 406).
+    pub fn preferred_content_type(&self, offered: &[&str]) -> Option<String> {
+        let Some(accept) = self.headers.get("accept") else {
+            return offered.first().map(|mime| mime.to_string());
+        };
+
+        let mut best: Option<(&str, f32)> = None;
+        for mime in offered {
+            let quality = accept_quality(accept, mime);
+            if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((mime, quality));
+            }
+        }
+        best.map(|(mime, _)| mime.to_string())
+    }
+
+    // (start, end) from "Range: bytes=start-end", end inclusive.
+    // "bytes=500-" gives (500, None). Suffix ranges ("bytes=-500") and
+    // multi-range requests aren't expressible here, see suffix_range
+    pub fn range(&self) -> Option<(u64, Option<u64>)> {
+        let (start, end) = self.single_byte_range()?;
+        let start = start.parse().ok()?;
+        let end = match end {
+            "" => None,
+            end => Some(end.parse().ok()?),
+        };
+        if end.is_some_and(|end| end < start) {
+            return None;
+        }
+        Some((start, end))
+    }
+
+    // N from "Range: bytes=-N", i.e. the last N bytes
+    pub fn suffix_range(&self) -> Option<u64> {
+        match self.single_byte_range()? {
+            ("", length) => length.parse().ok(),
+            _ => None,
+        }
+    }
+
+    // The "start" and "end" text of a single "bytes=start-end" range
+    fn single_byte_range(&self) -> Option<(&str, &str)> {
+        let header = self.headers.get("range")?;
+        let (unit, spec) = header.trim().split_once('=')?;
+        if !unit.trim().eq_ignore_ascii_case("bytes") || spec.contains(',') {
+            return None;
+        }
+        let (start, end) = spec.split_once('-')?;
+        Some((start.trim(), end.trim()))
+    }
+
+    // HTTP/1.0 clients know nothing of chunked encoding or 100-continue
+    pub fn is_http_1_0(&self) -> bool {
+        self.version == "HTTP/1.0"
+    }
+
+    // HTTP/1.1 keeps connections open unless the client asks to close;
+    // HTTP/1.0 closes unless the client explicitly asks for keep-alive
+    pub fn keep_alive(&self) -> bool {
+        let connection = self.get_header("Connection")
+            .map(|value| value.to_ascii_lowercase())
+            .unwrap_or_default();
+
+        if self.is_http_1_0() {
+            connection == "keep-alive"
         } else {
-            (full_route.to_string(), HashMap::<String, String>::new())
+            connection != "close"
         }
     }
 
-    fn extract_method_route_and_version(request: &str) -> Result<(String, String, String), String>
-    {
-        let parts: Vec<&str> = request.split("\r\n\r\n").collect();
-        let first_line_and_headers: Vec<&str> = parts[0].split("\r\n").collect();
+    fn first_query(&self, key: &str) -> Option<&String> {
+        self.query_params.get(key).and_then(|values| values.first())
+    }
 
-        if first_line_and_headers.is_empty() {
-            return Err("Empty request".to_string());
+    pub(crate) fn extract_query_params(full_route: &str)  -> (String, HashMap<String, Vec<String>>) {
+        if let Some((path, query_params_str)) = full_route.split_once("?") {
+            let mut query_params: HashMap<String, Vec<String>> = HashMap::new();
+
+            for param in query_params_str.split("&").filter(|param| !param.is_empty()) {
+                // Flag-style params (?debug) get an empty value; only the first '=' splits
+                let (param_name, param_value) = param.split_once("=").unwrap_or((param, ""));
+                query_params
+                    .entry(percent_decode(&param_name.replace('+', " ")))
+                    .or_default()
+                    .push(percent_decode(&param_value.replace('+', " ")));
+            }
+
+            (path.to_string(), query_params)
+        } else {
+            (full_route.to_string(), HashMap::<String, Vec<String>>::new())
         }
+    }
 
-        let first_line = first_line_and_headers[0];
-        let first_line_parts: Vec<&str> = first_line.split_whitespace().collect();
+    // Only looks at the first line, and never indexes, so junk input is an Err, not a panic
+    fn extract_method_route_and_version(head: &str) -> Result<(Method, String, String), String> {
+        if head.trim().is_empty() {
+            return Err("Empty request".to_string());
+        }
 
-        if first_line_parts.len() != 3 {
-            return Err("Invalid request line format".to_string());
+        let request_line = head.split("\r\n").next().unwrap_or_default();
+        if request_line.trim().is_empty() {
+            return Err("Missing request line".to_string());
         }
 
-        Ok((
-            first_line_parts[0].to_string(),
-            first_line_parts[1].to_string(),
-            first_line_parts[2].to_string(),
-        ))
+        let mut parts = request_line.split_whitespace();
+        let (method, route, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(route), Some(version), None) => (Method::from(method), route, version),
+            _ => return Err(format!("Invalid request line '{}', expected 'METHOD PATH VERSION'", request_line)),
+        };
+
+        Self::validate_target(&method, route)?;
+        Ok((method, route.to_string(), version.to_string()))
+    }
+
+    // Origin-form only ("/path?query"), plus "*" for OPTIONS. Control
+    // characters never reach routing or the logs.
+    fn validate_target(method: &Method, target: &str) -> Result<(), String> {
+        if target.bytes().any(|byte| byte.is_ascii_control()) {
+            return Err(format!("Request target {:?} contains control characters", target));
+        }
+        if target == "*" && *method == Method::Options {
+            return Ok(());
+        }
+        if !target.starts_with('/') {
+            return Err(format!("Request target {:?} must start with '/'", target));
+        }
+        Ok(())
     }
 
-    fn extract_headers(request: &str) -> HashMap<String, String> {
-        let parts: Vec<&str> = request.split("\r\n\r\n").collect();
-        let first_line_and_headers: Vec<&str> = parts[0].split("\r\n").collect();
-        let headers = &first_line_and_headers[1..];
+    fn extract_headers(head: &str) -> HashMap<String, String> {
         let mut headers_map = HashMap::new();
 
-        for header in headers {
+        // Everything after the request line
+        for header in head.split("\r\n").skip(1) {
             if let Some((header, value)) = header.split_once(':') {
                 headers_map.insert(
-                    header.trim().to_string(),
+                    header.trim().to_ascii_lowercase(),
                     value.trim().to_string(),
                 );
             }
@@ -134,4 +470,112 @@ impl HTTPRequest {
 
         headers_map
     }
-}
\ No newline at end of file
+}
+
+// RFC 3986 dot-segment removal, plus collapsing "//", so "/api//users",
+// "/api/./users" and "/api/x/../users" all become "/api/users". ".." stops
+// at the root, and a trailing slash is kept for TrailingSlash to act on.
+pub fn normalize_path(path: &str) -> String {
+    if !path.starts_with('/') {
+        return path.to_string();  // ← "*" for OPTIONS
+    }
+
+    let mut segments: Vec<&str> = Vec::new();
+    let mut trailing_slash = false;
+    for segment in path.split('/').skip(1) {
+        match segment {
+            "" | "." => trailing_slash = true,
+            ".." => {
+                segments.pop();
+                trailing_slash = true;
+            }
+            _ => {
+                segments.push(segment);
+                trailing_slash = false;
+            }
+        }
+    }
+
+    let mut normalized = format!("/{}", segments.join("/"));
+    if trailing_slash && !segments.is_empty() {
+        normalized.push('/');
+    }
+    normalized
+}
+
+fn merge_patch(target: &mut Value, patch: Map<String, Value>) {
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(fields) = target else { return };
+
+    for (key, value) in patch {
+        match value {
+            Value::Null => {
+                fields.remove(&key);
+            }
+            Value::Object(nested) => merge_patch(fields.entry(key).or_insert(Value::Null), nested),
+            value => {
+                fields.insert(key, value);
+            }
+        }
+    }
+}
+
+// q-value the Accept header gives `mime`, taken from the most specific
+// matching range: "text/html" beats "text/*" beats "*/*"
+fn accept_quality(accept: &str, mime: &str) -> f32 {
+    let (mime_type, mime_subtype) = mime.split_once('/').unwrap_or((mime, ""));
+    let mut best: Option<(u8, f32)> = None;
+
+    for range in accept.split(',') {
+        let mut parts = range.split(';');
+        let media_range = parts.next().unwrap_or("").trim();
+        let (range_type, range_subtype) = media_range.split_once('/').unwrap_or((media_range, ""));
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        let specificity = if range_type == "*" && range_subtype == "*" {
+            0
+        } else if range_type.eq_ignore_ascii_case(mime_type) && range_subtype == "*" {
+            1
+        } else if range_type.eq_ignore_ascii_case(mime_type) && range_subtype.eq_ignore_ascii_case(mime_subtype) {
+            2
+        } else {
+            continue;
+        };
+
+        if best.is_none_or(|(best_specificity, _)| specificity > best_specificity) {
+            best = Some((specificity, quality));
+        }
+    }
+
+    best.map_or(0.0, |(_, quality)| quality)
+}
+
+// Decode %XX escapes (e.g. "John%20Doe" -> "John Doe").
+// Malformed escapes are kept as-is instead of failing the request.
+pub fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit()
+        {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap();
+            decoded.push(u8::from_str_radix(hex, 16).unwrap());
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
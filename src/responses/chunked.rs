@@ -0,0 +1,54 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc::Receiver;
+
+// Body chunks produced after the handler returns. Shared so HTTPResponse
+// stays Clone; whoever writes the response takes the receiver.
+#[derive(Clone)]
+pub struct ChunkStream(Arc<Mutex<Option<Receiver<Vec<u8>>>>>);
+
+impl ChunkStream {
+    pub fn new(receiver: Receiver<Vec<u8>>) -> Self {
+        Self(Arc::new(Mutex::new(Some(receiver))))
+    }
+
+    fn take(&self) -> Option<Receiver<Vec<u8>>> {
+        self.0.lock().ok()?.take()
+    }
+}
+
+impl fmt::Debug for ChunkStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ChunkStream")
+    }
+}
+
+// Write each chunk as "<hex len>\r\n<data>\r\n", then the "0\r\n\r\n" terminator.
+// Unframed (for HTTP/1.0) writes the bare data; closing the connection ends the body.
+// The body ends when every sender has been dropped. Returns the payload bytes sent.
+pub(crate) async fn write_chunks<W: AsyncWrite + Unpin>(chunks: &ChunkStream, out: &mut W, framed: bool) -> std::io::Result<usize> {
+    let mut sent = 0;
+    if let Some(mut receiver) = chunks.take() {
+        while let Some(chunk) = receiver.recv().await {
+            // An empty chunk would read as the terminator
+            if chunk.is_empty() {
+                continue;
+            }
+            if framed {
+                out.write_all(format!("{:x}\r\n", chunk.len()).as_bytes()).await?;
+                out.write_all(&chunk).await?;
+                out.write_all(b"\r\n").await?;
+            } else {
+                out.write_all(&chunk).await?;
+            }
+            out.flush().await?;
+            sent += chunk.len();
+        }
+    }
+
+    if framed {
+        out.write_all(b"0\r\n\r\n").await?;
+    }
+    Ok(sent)
+}
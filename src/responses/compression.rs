@@ -0,0 +1,135 @@
+use std::io::Write;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use crate::responses::HTTPResponse;
+
+// Bodies smaller than this aren't worth the gzip overhead
+const MIN_COMPRESS_SIZE: usize = 256;
+
+// Already-compressed formats only get bigger
+const PRECOMPRESSED_TYPES: [&str; 6] = ["image/*", "video/*", "audio/*", "application/zip", "application/gzip", "font/woff*"];
+
+// Which responses get gzipped: bodies of at least `min_size` bytes whose
+// Content-Type is compressible. Without an allowlist that's everything but
+// the already-compressed formats above.
+#[derive(Debug, Clone)]
+pub struct CompressionOptions {
+    min_size: usize,
+    types: Option<Vec<String>>  // ← e.g. ["text/*", "application/json"]
+}
+
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        Self {
+            min_size: MIN_COMPRESS_SIZE,
+            types: None
+        }
+    }
+}
+
+impl CompressionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn min_size(mut self, bytes: usize) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    // Only compress these types ("text/*" covers every text subtype)
+    pub fn compressible_types<'a>(mut self, types: impl IntoIterator<Item = &'a str>) -> Self {
+        let allowed = self.types.get_or_insert_with(Vec::new);
+        allowed.extend(types.into_iter().map(str::to_ascii_lowercase));
+        self
+    }
+
+    fn allows_type(&self, content_type: &str) -> bool {
+        let media_type = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        match &self.types {
+            Some(allowed) => allowed.iter().any(|pattern| type_matches(pattern, &media_type)),
+            None => !PRECOMPRESSED_TYPES.iter().any(|pattern| type_matches(pattern, &media_type)),
+        }
+    }
+}
+
+impl HTTPResponse {
+    // Gzip the body if the client's Accept-Encoding allows it and the
+    // content is worth compressing
+    pub fn compress_for(self, accept_encoding: &str) -> Self {
+        self.compress_with(accept_encoding, &CompressionOptions::default())
+    }
+
+    // compress_for with a custom size threshold and type allowlist
+    pub fn compress_with(self, accept_encoding: &str, options: &CompressionOptions) -> Self {
+        if !accepts_gzip(accept_encoding) || !self.is_compressible(options) {
+            return self;
+        }
+        self.gzip()
+    }
+
+    // Gzip the body unconditionally, updating the framing headers
+    pub fn gzip(self) -> Self {
+        // Streamed files go out as they are
+        if self.file_body().is_some() {
+            return self;
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        let compressed = match encoder.write_all(self.body()).and_then(|_| encoder.finish()) {
+            Ok(compressed) => compressed,
+            Err(_) => return self,
+        };
+
+        let content_type = self.get_header("Content-Type")
+            .unwrap_or("application/octet-stream")
+            .to_string();
+
+        self.with_bytes_body(compressed, &content_type)
+            .with_header("Content-Encoding", "gzip")
+            .vary("Accept-Encoding")
+    }
+
+    fn is_compressible(&self, options: &CompressionOptions) -> bool {
+        // Streams go out chunk by chunk, and 1xx/204/304 and HEAD responses
+        // (body already dropped by without_body) have nothing to compress
+        let code = self.status.code();
+        if self.chunks().is_some() || (100..200).contains(&code) || code == 204 || code == 304 || self.body_omitted() {
+            return false;
+        }
+
+        if self.body().len() < options.min_size || self.get_header("Content-Encoding").is_some() {
+            return false;
+        }
+
+        // Content-Range offsets refer to the uncompressed bytes
+        if self.get_header("Content-Range").is_some() {
+            return false;
+        }
+
+        options.allows_type(self.get_header("Content-Type").unwrap_or(""))
+    }
+}
+
+// "text/*" and "font/woff*" match by prefix, anything else exactly
+fn type_matches(pattern: &str, media_type: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => media_type.starts_with(prefix),
+        None => media_type == pattern,
+    }
+}
+
+// True if Accept-Encoding lists gzip (or *) without q=0
+fn accepts_gzip(accept_encoding: &str) -> bool {
+    accept_encoding.split(',').any(|encoding| {
+        let mut parts = encoding.split(';');
+        let name = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+        let disabled = parts.any(|param| {
+            param.trim()
+                .strip_prefix("q=")
+                .and_then(|q| q.parse::<f32>().ok())
+                .is_some_and(|q| q == 0.0)
+        });
+
+        (name == "gzip" || name == "*") && !disabled
+    })
+}
@@ -0,0 +1,73 @@
+use crate::responses::{HTTPResponse, parse_http_date};
+
+impl HTTPResponse {
+    // Tag the response with an entity tag. `tag` may be given bare ("v1"),
+    // quoted ("\"v1\"") or weak ("W/\"v1\"")
+    pub fn with_etag(mut self, tag: &str) -> Self {
+        let tag = tag.trim();
+        let etag = if tag.starts_with('"') || tag.starts_with("W/\"") {
+            tag.to_string()
+        } else {
+            format!("\"{}\"", tag)
+        };
+        self.set_header("ETag", &etag);
+        self
+    }
+
+    // Swap a successful response for a bodyless 304 when the client's
+    // If-None-Match already covers its ETag
+    pub fn not_modified_for(self, if_none_match: &str) -> Self {
+        if !(200..300).contains(&self.status.code()) {
+            return self;
+        }
+        let Some(etag) = self.get_header("ETag") else {
+            return self;
+        };
+        if !etag_matches(if_none_match, etag) {
+            return self;
+        }
+        self.into_not_modified()
+    }
+
+    // Same for If-Modified-Since against Last-Modified, to the second. Only
+    // consulted when the request has no If-None-Match; unparseable dates
+    // on either side leave the response alone.
+    pub fn not_modified_since(self, if_modified_since: &str) -> Self {
+        if !(200..300).contains(&self.status.code()) {
+            return self;
+        }
+        let last_modified = self.get_header("Last-Modified").and_then(parse_http_date);
+        match (last_modified, parse_http_date(if_modified_since)) {
+            (Some(modified), Some(since)) if modified <= since => self.into_not_modified(),
+            _ => self,
+        }
+    }
+
+    // Keep the validators and caching headers, drop the body description
+    fn into_not_modified(self) -> Self {
+        let mut not_modified = HTTPResponse::new(304, "");
+        not_modified.headers = self.headers.into_iter()
+            .filter(|(name, _)| {
+                !name.eq_ignore_ascii_case("Content-Type") && !name.eq_ignore_ascii_case("Content-Encoding")
+            })
+            .collect();
+        not_modified
+    }
+}
+
+// If-None-Match uses the weak comparison: W/"x" and "x" are the same tag.
+// "*" matches any current representation.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let if_none_match = if_none_match.trim();
+    if if_none_match == "*" {
+        return true;
+    }
+
+    let etag = opaque_tag(etag);
+    if_none_match.split(',').any(|candidate| opaque_tag(candidate) == etag)
+}
+
+fn opaque_tag(tag: &str) -> &str {
+    let tag = tag.trim();
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
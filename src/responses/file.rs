@@ -0,0 +1,201 @@
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use crate::requests::HTTPRequest;
+use crate::responses::{HTTPResponse, HTTPStatus, format_http_date};
+
+// Files bigger than this are streamed in pieces of this size
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+// Part of a file on disk, read while the response is written
+#[derive(Debug, Clone)]
+pub struct FileBody {
+    path: PathBuf,
+    offset: u64,
+    pub(crate) len: u64
+}
+
+impl HTTPResponse {
+    // Serve a file from disk, Content-Type picked from the extension.
+    // Large files are streamed rather than read into memory; small ones are
+    // read in whole so they can still be compressed.
+    pub fn file(path: &Path) -> Self {
+        let len = match std::fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() => metadata.len(),
+            _ => return Self::not_found("File not found"),
+        };
+
+        let response = if len > STREAM_CHUNK_SIZE as u64 {
+            let body = FileBody { path: path.to_path_buf(), offset: 0, len };
+            Self::ok("").with_file_body(body, content_type_for(path))
+        } else {
+            match std::fs::read(path) {
+                Ok(contents) => Self::ok("").with_bytes_body(contents, content_type_for(path)),
+                Err(_) => return Self::not_found("File not found"),
+            }
+        };
+        let mut response = response.with_header("Accept-Ranges", "bytes");
+
+        if let Ok(modified) = std::fs::metadata(path).and_then(|metadata| metadata.modified()) {
+            response.set_header("Last-Modified", &format_http_date(modified));
+        }
+        match file_etag(path) {
+            Some(etag) => response.with_etag(&etag),
+            None => response,
+        }
+    }
+
+    // Serve `requested_path` (an already-decoded URL path, e.g. "css/site.css")
+    // from inside `root`.
+    // Any attempt to climb out of `root` with ".." is refused with 403.
+    pub fn serve_file(root: &str, requested_path: &str) -> Self {
+        let Some(relative) = sanitize_path(requested_path) else {
+            return Self::new(403, "Forbidden");
+        };
+
+        let root = Path::new(root);
+        let full_path = root.join(relative);
+
+        // Guard against symlinks pointing outside the root as well
+        if let (Ok(root), Ok(resolved)) = (root.canonicalize(), full_path.canonicalize())
+            && !resolved.starts_with(&root)
+        {
+            return Self::new(403, "Forbidden");
+        }
+
+        if !full_path.is_file() {
+            return Self::not_found("File not found");
+        }
+
+        Self::file(&full_path)
+    }
+
+    // Narrow a full 200 response down to the request's Range:
+    // 206 with the slice, or 416 if the range lies past the end.
+    // Anything else (no Range, unparseable, multi-range) keeps the full body.
+    pub fn with_range(self, req: &HTTPRequest) -> Self {
+        if self.status.code() != 200 || self.chunks().is_some() {
+            return self;
+        }
+
+        let len = self.body_len() as u64;
+        let (start, end) = if let Some((start, end)) = req.range() {
+            (start, end.map_or(len.saturating_sub(1), |end| end.min(len.saturating_sub(1))))
+        } else if let Some(suffix) = req.suffix_range() {
+            if suffix == 0 {
+                return range_not_satisfiable(len);
+            }
+            (len.saturating_sub(suffix), len.saturating_sub(1))
+        } else {
+            return self;
+        };
+
+        if start >= len {
+            return range_not_satisfiable(len);
+        }
+
+        let content_type = self.get_header("Content-Type")
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        // A streamed file just reads a narrower part of the file
+        let partial = match self.file_body().cloned() {
+            Some(file) => {
+                let narrowed = FileBody { offset: file.offset + start, len: end - start + 1, ..file };
+                self.with_file_body(narrowed, &content_type)
+            }
+            None => {
+                let slice = self.body()[start as usize..=end as usize].to_vec();
+                self.with_bytes_body(slice, &content_type)
+            }
+        };
+
+        let mut partial = partial
+            .with_header("Content-Range", &format!("bytes {}-{}/{}", start, end, len));
+        partial.status = HTTPStatus::PartialContent;
+        partial
+    }
+}
+
+// Copy `file` to `out` a chunk at a time. Returns the bytes sent; a file
+// that shrank since its length was taken is an error, as Content-Length
+// has already promised more.
+pub(crate) async fn write_file<W: AsyncWrite + Unpin>(file: &FileBody, out: &mut W) -> std::io::Result<usize> {
+    let mut source = tokio::fs::File::open(&file.path).await?;
+    source.seek(SeekFrom::Start(file.offset)).await?;
+
+    let mut chunk = vec![0; STREAM_CHUNK_SIZE];
+    let mut remaining = file.len;
+    while remaining > 0 {
+        let wanted = remaining.min(STREAM_CHUNK_SIZE as u64) as usize;
+        let n_bytes = source.read(&mut chunk[..wanted]).await?;
+        if n_bytes == 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "file shrank while being sent"));
+        }
+        out.write_all(&chunk[..n_bytes]).await?;
+        remaining -= n_bytes as u64;
+    }
+
+    out.flush().await?;
+    Ok(file.len as usize)
+}
+
+fn range_not_satisfiable(len: u64) -> HTTPResponse {
+    HTTPResponse::new(416, "Range Not Satisfiable")
+        .with_header("Content-Range", &format!("bytes */{}", len))
+}
+
+// Turn a URL path into a relative filesystem path, or None if it tries to escape
+fn sanitize_path(requested_path: &str) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+
+    for segment in requested_path.split('/') {
+        match segment {
+            "" | "." => continue,
+            ".." => return None,
+            s if s.contains('\\') || s.contains('\0') => return None,
+            s => relative.push(s),
+        }
+    }
+
+    Some(relative)
+}
+
+// Weak validator from size + mtime, so an edited file gets a new tag
+fn file_etag(path: &Path) -> Option<String> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?
+        .duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("W/\"{:x}-{:x}\"", metadata.len(), modified.as_secs()))
+}
+
+pub fn content_type_for(path: &Path) -> &'static str {
+    let extension = path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" | "mjs" => "application/javascript",
+        "json" => "application/json",
+        "txt" => "text/plain",
+        "xml" => "application/xml",
+        "csv" => "text/csv",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "webp" => "image/webp",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "wasm" => "application/wasm",
+        "mp4" => "video/mp4",
+        "mp3" => "audio/mpeg",
+        _ => "application/octet-stream",
+    }
+}
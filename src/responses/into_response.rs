@@ -0,0 +1,69 @@
+use serde::Serialize;
+use crate::responses::{HTTPResponse, HTTPStatus};
+
+// Anything a handler may return. Lets handlers return Result and use `?`:
+//   fn update(req: HTTPRequest) -> Result<HTTPResponse, HTTPResponse> {
+//       let user: User = req.body()?;   // ← Err(String) becomes a 400
+//       ...
+//   }
+// or plain values:  "pong"   (201, "done")   Json(user)
+pub trait IntoResponse {
+    fn into_response(self) -> HTTPResponse;
+}
+
+// Serialize any value as a 200 JSON body: Json(user), (201, Json(created))
+#[derive(Debug, Clone)]
+pub struct Json<T>(pub T);
+
+impl IntoResponse for HTTPResponse {
+    fn into_response(self) -> HTTPResponse {
+        self
+    }
+}
+
+// 200 text/plain
+impl IntoResponse for &str {
+    fn into_response(self) -> HTTPResponse {
+        HTTPResponse::ok(self)
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> HTTPResponse {
+        HTTPResponse::ok(&self)
+    }
+}
+
+// A value that can't be serialized is our bug, so 500
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> HTTPResponse {
+        HTTPResponse::ok_json(self.0).unwrap_or_else(|e| HTTPResponse::internal_error(&e))
+    }
+}
+
+// Same body and headers, different status: (201, "done"), (202, Json(job))
+impl<T: IntoResponse> IntoResponse for (u16, T) {
+    fn into_response(self) -> HTTPResponse {
+        let (code, body) = self;
+        let mut res = body.into_response();
+        res.status = HTTPStatus::from(code);
+        res
+    }
+}
+
+impl<T: IntoResponse, E: Into<HTTPResponse>> IntoResponse for Result<T, E> {
+    fn into_response(self) -> HTTPResponse {
+        match self {
+            Ok(success) => success.into_response(),
+            Err(error) => error.into(),
+        }
+    }
+}
+
+// The String errors from body(), param_parse(), query_params_as() and
+// multipart() all describe a bad request
+impl From<String> for HTTPResponse {
+    fn from(message: String) -> Self {
+        HTTPResponse::bad_request(&message)
+    }
+}
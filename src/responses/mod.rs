@@ -1,2 +1,17 @@
 pub mod response;
-pub use response::*;
\ No newline at end of file
+pub mod file;
+pub mod cookie;
+pub mod compression;
+pub mod chunked;
+pub mod sse;
+pub mod etag;
+pub mod http_date;
+pub mod into_response;
+pub use response::*;
+pub use file::*;
+pub use cookie::*;
+pub use chunked::ChunkStream;
+pub use sse::SseEvent;
+pub use compression::CompressionOptions;
+pub use into_response::{IntoResponse, Json};
+pub use http_date::{format_http_date, parse_http_date};
\ No newline at end of file
@@ -1,7 +1,10 @@
-use std::collections::HashMap;
 use serde::{Serialize};
+use tokio::sync::mpsc::Receiver;
+use crate::responses::{ChunkStream, Cookie, FileBody};
+use crate::websocket::OnUpgrade;
 
 #[derive(Debug, Clone)]
+#[repr(u16)]
 pub enum HTTPStatus {
     // 1xx Informational
     Continue = 100,
@@ -74,6 +77,9 @@ pub enum HTTPStatus {
     LoopDetected = 508,
     NotExtended = 510,
     NetworkAuthenticationRequired = 511,
+
+    // Any other code in 100-599 (extensions, vendor codes)
+    Other(u16),
 }
 
 impl HTTPStatus {
@@ -150,6 +156,15 @@ impl HTTPStatus {
             Self::LoopDetected => "Loop Detected",
             Self::NotExtended => "Not Extended",
             Self::NetworkAuthenticationRequired => "Network Authentication Required",
+
+            // Unregistered codes get the generic phrase for their class
+            Self::Other(code) => match code / 100 {
+                1 => "Informational",
+                2 => "Success",
+                3 => "Redirection",
+                4 => "Client Error",
+                _ => "Server Error",
+            },
         }
     }
 
@@ -226,6 +241,8 @@ impl HTTPStatus {
             Self::LoopDetected => 508,
             Self::NotExtended => 510,
             Self::NetworkAuthenticationRequired => 511,
+
+            Self::Other(code) => *code,
         }
     }
 }
@@ -305,7 +322,10 @@ impl From<u16> for HTTPStatus {
             510 => Self::NotExtended,
             511 => Self::NetworkAuthenticationRequired,
 
-            // Default to 500 for unknown codes
+            // Unregistered but valid codes keep their number
+            _ if (100..=599).contains(&code) => Self::Other(code),
+
+            // Not a valid status code at all
             _ => Self::InternalServerError,
         }
     }
@@ -314,21 +334,31 @@ impl From<u16> for HTTPStatus {
 #[derive(Debug, Clone)]
 pub struct HTTPResponse {
     pub status: HTTPStatus,
-    pub headers: HashMap<String, String>,
-    body: String,  // ← Always store as String internally
+    pub headers: Vec<(String, String)>,  // ← Ordered, names may repeat
+    body: Vec<u8>,  // ← Raw bytes so binary payloads survive
+    omitted_body_len: Option<usize>,  // ← Set by without_body so HEAD keeps the GET length
+    chunks: Option<ChunkStream>,  // ← Streamed body written after the headers
+    file: Option<Box<FileBody>>,  // ← Large file streamed from disk (boxed to keep responses small)
+    upgrade: Option<OnUpgrade>,   // ← Takes over the connection after a 101
+    generated: bool,              // ← The server/router's own error (no route, 405, panic), see error_page
 }
 
 impl HTTPResponse {
     // Create with plain text
     pub fn new(status_code: u16, body: &str) -> Self {
-        let mut headers = HashMap::new();
-        headers.insert("Content-Type".to_string(), "text/plain".to_string());
-        headers.insert("Content-Length".to_string(), body.len().to_string());
+        let headers = vec![
+            ("Content-Type".to_string(), "text/plain".to_string()),
+        ];
 
         Self {
             status: HTTPStatus::from(status_code),
             headers,
-            body: body.to_string(),
+            body: body.as_bytes().to_vec(),
+            omitted_body_len: None,
+            chunks: None,
+            file: None,
+            upgrade: None,
+            generated: false,
         }
     }
 
@@ -337,17 +367,41 @@ impl HTTPResponse {
         let json_body = serde_json::to_string(&data)
             .map_err(|e| format!("JSON serialization error: {}", e))?;
 
-        let mut headers = HashMap::new();
-        headers.insert("Content-Type".to_string(), "application/json".to_string());
-        headers.insert("Content-Length".to_string(), json_body.len().to_string());
+        let headers = vec![
+            ("Content-Type".to_string(), "application/json".to_string()),
+        ];
 
         Ok(Self {
             status: HTTPStatus::from(status_code),
             headers,
-            body: json_body,
+            body: json_body.into_bytes(),
+            omitted_body_len: None,
+            chunks: None,
+            file: None,
+            upgrade: None,
+            generated: false,
         })
     }
 
+    // Ad-hoc JSON built with json!() or merged at runtime. A Value always
+    // serializes, so unlike json() this can't fail.
+    pub fn json_value(status_code: u16, value: serde_json::Value) -> Self {
+        Self::new(status_code, "")
+            .with_bytes_body(value.to_string().into_bytes(), "application/json")
+    }
+
+    // Machine-readable error: {"error": {"code": 400, "message": "..."}}
+    pub fn error_json(status_code: u16, message: &str) -> Self {
+        Self::json_value(status_code, serde_json::json!({
+            "error": { "code": status_code, "message": message }
+        }))
+    }
+
+    // Create with raw bytes (application/octet-stream)
+    pub fn from_bytes(status_code: u16, body: Vec<u8>) -> Self {
+        Self::new(status_code, "").with_bytes_body(body, "application/octet-stream")
+    }
+
     // Convenience methods
     pub fn ok(body: &str) -> Self {
         Self::new(200, body)
@@ -357,36 +411,233 @@ impl HTTPResponse {
         Self::json(200, data)
     }
 
+    pub fn created(body: &str) -> Self {
+        Self::new(201, body)
+    }
+
+    // 204 carries no body at all
+    pub fn no_content() -> Self {
+        Self::new(204, "")
+    }
+
+    pub fn bad_request(message: &str) -> Self {
+        Self::new(400, message)
+    }
+
+    pub fn unauthorized(message: &str) -> Self {
+        Self::new(401, message)
+    }
+
+    pub fn forbidden(message: &str) -> Self {
+        Self::new(403, message)
+    }
+
     pub fn not_found(body: &str) -> Self {
         Self::new(404, body)
     }
 
-    // Builder methods
+    pub fn internal_error(message: &str) -> Self {
+        Self::new(500, message)
+    }
+
+    pub fn service_unavailable(message: &str) -> Self {
+        Self::new(503, message)
+    }
+
+    // Body of unknown length, sent with Transfer-Encoding: chunked as chunks
+    // arrive on `receiver`. Drop the sender to end the response.
+    pub fn chunked(receiver: Receiver<Vec<u8>>) -> Self {
+        let mut res = Self::ok("").with_header("Transfer-Encoding", "chunked");
+        res.chunks = Some(ChunkStream::new(receiver));
+        res
+    }
+
+    pub fn chunks(&self) -> Option<&ChunkStream> {
+        self.chunks.as_ref()
+    }
+
+    pub fn file_body(&self) -> Option<&FileBody> {
+        self.file.as_deref()
+    }
+
+    // Body read from disk while it's being sent; Content-Length is `file.len`
+    pub(crate) fn with_file_body(mut self, file: FileBody, content_type: &str) -> Self {
+        self.set_header("Content-Type", content_type);
+        self.body.clear();
+        self.file = Some(Box::new(file));
+        self
+    }
+
+    // Mark an error the framework produced itself, as opposed to one a
+    // handler chose to return, so HTTPServer::error_page may restyle it
+    pub(crate) fn generated(mut self) -> Self {
+        self.generated = true;
+        self
+    }
+
+    pub(crate) fn is_generated(&self) -> bool {
+        self.generated
+    }
+
+    pub(crate) fn with_upgrade(mut self, upgrade: OnUpgrade) -> Self {
+        self.upgrade = Some(upgrade);
+        self
+    }
+
+    pub(crate) fn upgrade(&self) -> Option<&OnUpgrade> {
+        self.upgrade.as_ref()
+    }
+
+    // Redirects (empty body, Location header set)
+    pub fn redirect(location: &str) -> Self {
+        Self::redirect_with_status(302, location)
+    }
+
+    pub fn redirect_permanent(location: &str) -> Self {
+        Self::redirect_with_status(301, location)
+    }
+
+    pub fn redirect_temporary(location: &str) -> Self {
+        Self::redirect_with_status(307, location)
+    }
+
+    // For 308 Permanent Redirect or any other 3xx code
+    pub fn redirect_with_status(status_code: u16, location: &str) -> Self {
+        Self::new(status_code, "").with_header("Location", location)
+    }
+
+    // Builder methods (appends, so repeated headers are kept)
     pub fn with_header(mut self, key: &str, value: &str) -> Self {
-        self.headers.insert(key.to_string(), value.to_string());
+        self.headers.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    // First value of a header, matched case-insensitively
+    pub fn get_header(&self, key: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Replace every existing value of a header with a single one
+    pub fn set_header(&mut self, key: &str, value: &str) {
+        self.remove_header(key);
+        self.headers.push((key.to_string(), value.to_string()));
+    }
+
+    // Add `header` to Vary, merging with any existing Vary values into one
+    // header, e.g. .vary("Accept").vary("Accept-Encoding") → "Accept, Accept-Encoding"
+    pub fn vary(mut self, header: &str) -> Self {
+        let mut values: Vec<String> = self.headers.iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("Vary"))
+            .flat_map(|(_, value)| value.split(','))
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .collect();
+
+        // "*" already means "varies on everything"
+        if !values.iter().any(|value| value == "*" || value.eq_ignore_ascii_case(header)) {
+            values.push(header.to_string());
+        }
+        self.set_header("Vary", &values.join(", "));
         self
     }
 
+    pub fn remove_header(&mut self, key: &str) {
+        self.headers.retain(|(name, _)| !name.eq_ignore_ascii_case(key));
+    }
+
+    pub fn with_cookie(self, name: &str, value: &str) -> Self {
+        self.with_set_cookie(Cookie::new(name, value))
+    }
+
+    // For cookies with attributes (Path, Max-Age, HttpOnly, ...)
+    pub fn with_set_cookie(self, cookie: Cookie) -> Self {
+        self.with_header("Set-Cookie", &cookie.to_string())
+    }
+
     pub fn with_content_type(mut self, content_type: &str) -> Self {
-        self.headers.insert("Content-Type".to_string(), content_type.to_string());
+        self.set_header("Content-Type", content_type);
         self
     }
 
     // NEW: HTML helper
     pub fn with_html_body(mut self, html: &str) -> Self {
-        self.body = html.to_string();
-        self.headers.insert("Content-Type".to_string(), "text/html".to_string());
-        self.headers.insert("Content-Length".to_string(), html.len().to_string());
+        self.body = html.as_bytes().to_vec();
+        self.set_header("Content-Type", "text/html");
         self
     }
 
-    // Convert to HTTP string
-    pub fn to_http_string(&self) -> String {
+    pub fn with_text_body(self, text: &str) -> Self {
+        self.with_bytes_body(text.as_bytes().to_vec(), "text/plain; charset=utf-8")
+    }
+
+    // Drop the body but still advertise its Content-Length, e.g. for HEAD
+    pub fn without_body(mut self) -> Self {
+        self.omitted_body_len.get_or_insert(self.body_len());
+        self.body.clear();
+        self.chunks = None;
+        self.file = None;
+        self
+    }
+
+    // True once without_body has dropped the body
+    pub(crate) fn body_omitted(&self) -> bool {
+        self.omitted_body_len.is_some()
+    }
+
+    // Empty for streamed bodies (chunks or file), see body_len
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    // Bytes the body will have on the wire, streamed files included
+    pub fn body_len(&self) -> usize {
+        self.file.as_ref().map_or(self.body.len(), |file| file.len as usize)
+    }
+
+    // Raw bytes body (images, downloads, anything non-UTF-8)
+    pub fn with_bytes_body(mut self, body: Vec<u8>, content_type: &str) -> Self {
+        self.set_header("Content-Type", content_type);
+        self.body = body;
+        self.file = None;
+        self
+    }
+
+    // None when the response mustn't carry one: chunked bodies, 1xx, 204 and 304
+    fn content_length(&self) -> Option<usize> {
+        let chunked = self.get_header("Transfer-Encoding")
+            .is_some_and(|encoding| encoding.to_ascii_lowercase().contains("chunked"));
+        let code = self.status.code();
+        if chunked || self.chunks.is_some() || (100..200).contains(&code) || code == 204 || code == 304 {
+            return None;
+        }
+        Some(self.omitted_body_len.unwrap_or(self.body_len()))
+    }
+
+    // Convert to wire bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
         let first_line = format!("HTTP/1.1 {} {}", self.status.code(), self.status.status_text());
         let mut headers_string = String::new();
         for (key, value) in self.headers.iter() {
+            // Always recomputed below so it can't go stale
+            if key.eq_ignore_ascii_case("Content-Length") {
+                continue;
+            }
             headers_string.push_str(&format!("{}: {}\r\n", key, value));
         }
-        format!("{}\r\n{}\r\n{}", first_line, headers_string, self.body)
+        if let Some(length) = self.content_length() {
+            headers_string.push_str(&format!("Content-Length: {}\r\n", length));
+        }
+
+        let mut bytes = format!("{}\r\n{}\r\n", first_line, headers_string).into_bytes();
+        bytes.extend_from_slice(&self.body);
+        bytes
     }
-}
\ No newline at end of file
+
+    // Convert to HTTP string (lossy for binary bodies, prefer to_bytes)
+    pub fn to_http_string(&self) -> String {
+        String::from_utf8_lossy(&self.to_bytes()).into_owned()
+    }
+}
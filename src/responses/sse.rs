@@ -0,0 +1,89 @@
+use tokio::sync::mpsc::{self, Receiver};
+use crate::responses::HTTPResponse;
+
+// One Server-Sent Event. Multi-line data is split over several "data:"
+// lines, which the browser's EventSource joins back together.
+//   sender.send(SseEvent::data("42").event("count").id("7")).await
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SseEvent {
+    data: String,
+    event: Option<String>,  // ← Dispatched as this type instead of "message"
+    id: Option<String>,     // ← Sent back by the browser as Last-Event-ID on reconnect
+    retry: Option<u64>      // ← Reconnect delay in milliseconds
+}
+
+impl SseEvent {
+    pub fn data(data: &str) -> Self {
+        Self { data: data.to_string(), ..Self::default() }
+    }
+
+    pub fn event(mut self, name: &str) -> Self {
+        self.event = Some(name.to_string());
+        self
+    }
+
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    pub fn retry(mut self, millis: u64) -> Self {
+        self.retry = Some(millis);
+        self
+    }
+
+    // Wire form, ending in the blank line that dispatches the event.
+    // Newlines in event/id would end the field early, so they're dropped.
+    pub fn to_frame(&self) -> String {
+        let single_line = |value: &str| value.replace(['\r', '\n'], "");
+        let mut frame = String::new();
+        if let Some(event) = &self.event {
+            frame.push_str(&format!("event: {}\n", single_line(event)));
+        }
+        if let Some(id) = &self.id {
+            frame.push_str(&format!("id: {}\n", single_line(id)));
+        }
+        if let Some(retry) = self.retry {
+            frame.push_str(&format!("retry: {}\n", retry));
+        }
+        // EventSource ends a line at CRLF, LF or a lone CR, so all three split
+        for line in self.data.replace("\r\n", "\n").replace('\r', "\n").split('\n') {
+            frame.push_str(&format!("data: {}\n", line));
+        }
+        frame.push('\n');
+        frame
+    }
+}
+
+impl From<&str> for SseEvent {
+    fn from(data: &str) -> Self {
+        Self::data(data)
+    }
+}
+
+impl From<String> for SseEvent {
+    fn from(data: String) -> Self {
+        Self::data(&data)
+    }
+}
+
+impl HTTPResponse {
+    // text/event-stream response that sends each event on `receiver` as its
+    // own chunk, flushed right away. Drop the sender to end the stream; if
+    // the client goes away first, sends start failing. Must be called from
+    // within the Tokio runtime (handlers always are).
+    pub fn event_stream(mut receiver: Receiver<SseEvent>) -> Self {
+        let (sender, chunks) = mpsc::channel(1);
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                if sender.send(event.to_frame().into_bytes()).await.is_err() {
+                    break; // Client went away
+                }
+            }
+        });
+
+        let mut res = HTTPResponse::chunked(chunks).with_header("Cache-Control", "no-cache");
+        res.set_header("Content-Type", "text/event-stream");
+        res
+    }
+}
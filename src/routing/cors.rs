@@ -0,0 +1,132 @@
+use std::fmt;
+use std::sync::Arc;
+use crate::requests::{HTTPRequest, Method};
+use crate::responses::HTTPResponse;
+
+type OriginCheck = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+// Cross-origin config. Register both halves:
+//   .add_middleware(cors.preflight())      ← answers OPTIONS preflights with 204
+//   .add_response_middleware(cors.headers()) ← adds Access-Control-Allow-Origin
+// Requests from origins that aren't allowed get no CORS headers at all
+// (preflights get a 403), so the browser blocks them.
+#[derive(Clone, Default)]
+pub struct Cors {
+    origins: Vec<String>,
+    origin_check: Option<OriginCheck>,  // ← Dynamic allow-list, asked after `origins`
+    methods: Vec<String>,
+    headers: Vec<String>,
+    max_age: Option<u64>
+}
+
+impl Cors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Builder methods ("*" allows any origin)
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        self.origins.push(origin.to_string());
+        self
+    }
+
+    pub fn allow_origins<'a>(mut self, origins: impl IntoIterator<Item = &'a str>) -> Self {
+        self.origins.extend(origins.into_iter().map(str::to_string));
+        self
+    }
+
+    // For origins that can't be listed up front, e.g. any *.example.com
+    pub fn allow_origin_fn<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.origin_check = Some(Arc::new(check));
+        self
+    }
+
+    pub fn allow_methods<'a>(mut self, methods: impl IntoIterator<Item = &'a str>) -> Self {
+        self.methods.extend(methods.into_iter().map(|method| method.to_ascii_uppercase()));
+        self
+    }
+
+    pub fn allow_headers<'a>(mut self, headers: impl IntoIterator<Item = &'a str>) -> Self {
+        self.headers.extend(headers.into_iter().map(str::to_string));
+        self
+    }
+
+    // How long browsers may cache a preflight result
+    pub fn max_age(mut self, seconds: u64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    // Request-phase half: short-circuits preflight requests
+    pub fn preflight(&self) -> impl Fn(HTTPRequest) -> Result<HTTPRequest, HTTPResponse> + Send + Sync + 'static {
+        let cors = self.clone();
+        move |req: HTTPRequest| {
+            // A preflight is an OPTIONS with Origin + Access-Control-Request-Method
+            let is_preflight = req.method == Method::Options && req.get_header("Access-Control-Request-Method").is_some();
+            let Some(origin) = req.get_header("Origin").filter(|_| is_preflight) else {
+                return Ok(req);
+            };
+
+            let Some(allowed_origin) = cors.allowed_origin(&origin) else {
+                return Err(HTTPResponse::new(403, "CORS origin not allowed"));
+            };
+
+            let mut res = HTTPResponse::new(204, "")
+                .with_header("Access-Control-Allow-Origin", &allowed_origin)
+                .vary("Origin");
+            if !cors.methods.is_empty() {
+                res = res.with_header("Access-Control-Allow-Methods", &cors.methods.join(", "));
+            }
+            if !cors.headers.is_empty() {
+                res = res.with_header("Access-Control-Allow-Headers", &cors.headers.join(", "));
+            }
+            if let Some(max_age) = cors.max_age {
+                res = res.with_header("Access-Control-Max-Age", &max_age.to_string());
+            }
+            Err(res)
+        }
+    }
+
+    // Response-phase half: tags responses to allowed cross-origin requests
+    pub fn headers(&self) -> impl Fn(&HTTPRequest, HTTPResponse) -> HTTPResponse + Send + Sync + 'static {
+        let cors = self.clone();
+        move |req: &HTTPRequest, res: HTTPResponse| {
+            // Preflight responses already carry their headers
+            if res.get_header("Access-Control-Allow-Origin").is_some() {
+                return res;
+            }
+
+            match req.get_header("Origin").and_then(|origin| cors.allowed_origin(&origin)) {
+                Some(allowed_origin) => res
+                    .with_header("Access-Control-Allow-Origin", &allowed_origin)
+                    .vary("Origin"),
+                None => res,
+            }
+        }
+    }
+
+    // Value for Access-Control-Allow-Origin, or None if `origin` isn't allowed
+    fn allowed_origin(&self, origin: &str) -> Option<String> {
+        if self.origins.iter().any(|allowed| allowed == "*") {
+            return Some("*".to_string());
+        }
+        let listed = self.origins.iter().any(|allowed| allowed.eq_ignore_ascii_case(origin));
+        let accepted = listed || self.origin_check.as_ref().is_some_and(|check| check(origin));
+        accepted.then(|| origin.to_string())
+    }
+}
+
+impl fmt::Debug for Cors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cors")
+            .field("origins", &self.origins)
+            .field("origin_check", &self.origin_check.is_some())
+            .field("methods", &self.methods)
+            .field("headers", &self.headers)
+            .field("max_age", &self.max_age)
+            .finish()
+    }
+}
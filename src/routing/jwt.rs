@@ -0,0 +1,171 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+use crate::requests::HTTPRequest;
+use crate::responses::HTTPResponse;
+use crate::security::{hmac_sha256, verify_hmac_sha256};
+
+// Signing algorithms JwtAuth can check. A token whose header names any
+// other algorithm (including "none") is rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JwtAlgorithm {
+    #[default]
+    HS256
+}
+
+impl JwtAlgorithm {
+    fn name(self) -> &'static str {
+        match self {
+            Self::HS256 => "HS256",
+        }
+    }
+
+    fn sign(self, secret: &[u8], input: &[u8]) -> Vec<u8> {
+        match self {
+            Self::HS256 => hmac_sha256(secret, input).to_vec(),
+        }
+    }
+
+    fn verify(self, secret: &[u8], input: &[u8], signature: &[u8]) -> bool {
+        match self {
+            Self::HS256 => verify_hmac_sha256(secret, input, signature),
+        }
+    }
+}
+
+// The payload of a verified token. JwtAuth stores it as a request extension:
+//   let user = req.extension::<JwtClaims>().and_then(|claims| claims.subject());
+#[derive(Debug, Clone, PartialEq)]
+pub struct JwtClaims(Map<String, Value>);
+
+impl JwtClaims {
+    pub fn get(&self, claim: &str) -> Option<&Value> {
+        self.0.get(claim)
+    }
+
+    pub fn get_str(&self, claim: &str) -> Option<&str> {
+        self.get(claim).and_then(Value::as_str)
+    }
+
+    // The "sub" claim, usually a user id
+    pub fn subject(&self) -> Option<&str> {
+        self.get_str("sub")
+    }
+
+    // All claims as an app-defined struct
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, String> {
+        serde_json::from_value(Value::Object(self.0.clone()))
+            .map_err(|e| format!("Failed to deserialize token claims: {}", e))
+    }
+}
+
+// Bearer-token auth with signed JSON Web Tokens. Requests with a valid,
+// unexpired token get its JwtClaims; everything else gets a 401 saying why.
+//   let auth = JwtAuth::new(secret).algorithm(JwtAlgorithm::HS256);
+//   .add_middleware(auth.middleware())
+#[derive(Clone)]
+pub struct JwtAuth {
+    secret: Vec<u8>,
+    algorithm: JwtAlgorithm
+}
+
+impl JwtAuth {
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            secret: secret.as_ref().to_vec(),
+            algorithm: JwtAlgorithm::default()
+        }
+    }
+
+    pub fn algorithm(mut self, algorithm: JwtAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn middleware(&self) -> impl Fn(HTTPRequest) -> Result<HTTPRequest, HTTPResponse> + Send + Sync + 'static {
+        let auth = self.clone();
+        move |req: HTTPRequest| auth.check(req)
+    }
+
+    // Verify the request's bearer token and attach its claims, or 401
+    pub fn check(&self, mut req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
+        let claims = req.bearer_token()
+            .ok_or_else(|| "Missing bearer token".to_string())
+            .and_then(|token| self.verify(&token))
+            .map_err(|message| HTTPResponse::unauthorized(&message).with_header("WWW-Authenticate", "Bearer"))?;
+
+        req.insert_extension(claims);
+        Ok(req)
+    }
+
+    // Signature first, then the time claims. `exp` is required; `nbf` is
+    // checked when present. Errors are fit to send back to the client.
+    pub fn verify(&self, token: &str) -> Result<JwtClaims, String> {
+        let malformed = || "Malformed token".to_string();
+
+        let mut segments = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (segments.next(), segments.next(), segments.next(), segments.next()) else {
+            return Err(malformed());
+        };
+
+        let algorithm = decode_segment(header).ok_or_else(malformed)?
+            .get("alg")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        if algorithm.as_deref() != Some(self.algorithm.name()) {
+            return Err(format!("Token must be signed with {}", self.algorithm.name()));
+        }
+
+        let signature = BASE64URL.decode(signature).map_err(|_| malformed())?;
+        if !self.algorithm.verify(&self.secret, format!("{}.{}", header, payload).as_bytes(), &signature) {
+            return Err("Invalid token signature".to_string());
+        }
+
+        let Some(Value::Object(claims)) = decode_segment(payload) else {
+            return Err(malformed());
+        };
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |elapsed| elapsed.as_secs_f64());
+        match claims.get("exp").map(Value::as_f64) {
+            None => return Err("Token has no expiry".to_string()),
+            Some(None) => return Err(malformed()),
+            Some(Some(exp)) if now >= exp => return Err("Token expired".to_string()),
+            Some(Some(_)) => {}
+        }
+        match claims.get("nbf").map(Value::as_f64) {
+            Some(None) => return Err(malformed()),
+            Some(Some(nbf)) if now < nbf => return Err("Token not yet valid".to_string()),
+            _ => {}
+        }
+
+        Ok(JwtClaims(claims))
+    }
+
+    // Issue a token this JwtAuth accepts (remember to include "exp")
+    pub fn encode(&self, claims: &impl Serialize) -> Result<String, String> {
+        let header = json!({ "alg": self.algorithm.name(), "typ": "JWT" });
+        let payload = serde_json::to_vec(claims)
+            .map_err(|e| format!("Failed to serialize token claims: {}", e))?;
+
+        let signing_input = format!("{}.{}", BASE64URL.encode(header.to_string()), BASE64URL.encode(payload));
+        let signature = self.algorithm.sign(&self.secret, signing_input.as_bytes());
+        Ok(format!("{}.{}", signing_input, BASE64URL.encode(signature)))
+    }
+}
+
+// Keeps the secret out of logs
+impl fmt::Debug for JwtAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtAuth").field("algorithm", &self.algorithm).finish_non_exhaustive()
+    }
+}
+
+fn decode_segment(segment: &str) -> Option<Value> {
+    let bytes = BASE64URL.decode(segment).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
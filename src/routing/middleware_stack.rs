@@ -0,0 +1,43 @@
+use super::Middleware;
+
+// A reusable set of route middleware, run in the order added. Build it once
+// and hand it to the *_with route builders wherever the same checks apply:
+//   let admin = MiddlewareStack::new().then(admin_check).then(rate_limit);
+//   router.get_with("/admin", dashboard, &admin)
+// (The plain builders keep taking a Vec, so vec![single_fn] still coerces.)
+#[derive(Debug, Clone, Default)]
+pub struct MiddlewareStack {
+    layers: Vec<Middleware>
+}
+
+impl MiddlewareStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn then(mut self, middleware: Middleware) -> Self {
+        self.layers.push(middleware);
+        self
+    }
+}
+
+impl From<Vec<Middleware>> for MiddlewareStack {
+    fn from(layers: Vec<Middleware>) -> Self {
+        Self { layers }
+    }
+}
+
+impl From<MiddlewareStack> for Vec<Middleware> {
+    fn from(stack: MiddlewareStack) -> Self {
+        stack.layers
+    }
+}
+
+impl IntoIterator for MiddlewareStack {
+    type Item = Middleware;
+    type IntoIter = std::vec::IntoIter<Middleware>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.layers.into_iter()
+    }
+}
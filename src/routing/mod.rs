@@ -1,3 +1,12 @@
 pub mod route;
+pub mod cors;
+pub mod rate_limit;
+pub mod request_id;
+pub mod middleware_stack;
+pub mod jwt;
 pub use route::*;
-
+pub use cors::*;
+pub use rate_limit::*;
+pub use request_id::*;
+pub use middleware_stack::*;
+pub use jwt::*;
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::requests::HTTPRequest;
+use crate::responses::HTTPResponse;
+
+// Idle buckets are dropped once this many clients are being tracked
+const PRUNE_THRESHOLD: usize = 10_000;
+
+// Token bucket per client IP: `max_requests` burst, refilled evenly over `per`.
+// Clones share the same buckets.
+//   .add_middleware(limiter.middleware())
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    max_requests: u32,
+    per: Duration,
+    buckets: Arc<Mutex<HashMap<IpAddr, Bucket>>>
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, per: Duration) -> Self {
+        Self {
+            max_requests,
+            per,
+            buckets: Arc::new(Mutex::new(HashMap::new()))
+        }
+    }
+
+    pub fn middleware(&self) -> impl Fn(HTTPRequest) -> Result<HTTPRequest, HTTPResponse> + Send + Sync + 'static {
+        let limiter = self.clone();
+        move |req: HTTPRequest| limiter.check(req)
+    }
+
+    // Spend one token for the request's IP, or 429 with Retry-After if the bucket is empty.
+    // Requests without a remote address (e.g. serve_connection) aren't limited.
+    pub fn check(&self, req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
+        let Some(ip) = req.remote_addr.map(|addr| addr.ip()) else {
+            return Ok(req);
+        };
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return Ok(req);
+        };
+
+        let now = Instant::now();
+        let capacity = f64::from(self.max_requests);
+        let refill_per_sec = capacity / self.per.as_secs_f64();
+
+        if buckets.len() > PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| bucket.tokens_at(now, refill_per_sec, capacity) < capacity);
+        }
+
+        let bucket = buckets.entry(ip).or_insert(Bucket { tokens: capacity, refilled_at: now });
+        bucket.tokens = bucket.tokens_at(now, refill_per_sec, capacity);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(req);
+        }
+
+        // Whole seconds until the next token, at least 1
+        let retry_after = ((1.0 - bucket.tokens) / refill_per_sec).ceil().max(1.0) as u64;
+        Err(HTTPResponse::new(429, "Too Many Requests")
+            .with_header("Retry-After", &retry_after.to_string()))
+    }
+}
+
+impl Bucket {
+    fn tokens_at(&self, now: Instant, refill_per_sec: f64, capacity: f64) -> f64 {
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        (self.tokens + elapsed * refill_per_sec).min(capacity)
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::requests::HTTPRequest;
+use crate::responses::HTTPResponse;
+
+// Longer incoming IDs are replaced with a generated one
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// Per-request ID, stored as a request extension. Register both halves on the server:
+//   .add_middleware(request_id)                   ← reads X-Request-Id or generates one
+//   .add_response_middleware(request_id_header)   ← echoes it back as X-Request-Id
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(pub String);
+
+pub fn request_id(mut req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
+    let id = req.get_header("X-Request-Id")
+        .filter(|id| is_valid_request_id(id))
+        .unwrap_or_else(generate_request_id);
+    req.insert_extension(RequestId(id));
+    Ok(req)
+}
+
+pub fn request_id_header(req: &HTTPRequest, res: HTTPResponse) -> HTTPResponse {
+    match req.extension::<RequestId>() {
+        Some(RequestId(id)) => res.with_header("X-Request-Id", id),
+        None => res,
+    }
+}
+
+// Millisecond timestamp + a counter: unique without pulling in a UUID crate
+fn generate_request_id() -> String {
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or_default();
+    let sequence = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    format!("{:x}-{:06x}", now_ms, sequence)
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.bytes().all(|byte| byte.is_ascii_graphic())
+}
@@ -1,31 +1,144 @@
-use crate::requests::HTTPRequest;
-use crate::responses::HTTPResponse;
+use std::fmt;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use regex::Regex;
+use crate::requests::{HTTPRequest, Method, percent_decode};
+use crate::responses::{HTTPResponse, IntoResponse};
+use crate::websocket::{self, WebSocket, WebSocketHandler};
+use super::MiddlewareStack;
 
-type Handler = fn(HTTPRequest) -> HTTPResponse;
+// Shared so closures can capture app state (pools, counters, config).
+// Every handler is async underneath; sync ones resolve immediately.
+pub type Handler = Arc<dyn Fn(HTTPRequest) -> HandlerFuture + Send + Sync>;
+pub type HandlerFuture = Pin<Box<dyn Future<Output = HTTPResponse> + Send>>;
 pub type Middleware = fn(HTTPRequest) -> Result<HTTPRequest, HTTPResponse>;
+// Router/server-level middleware can also be a closure carrying config (e.g. Cors)
+pub type SharedMiddleware = Arc<dyn Fn(HTTPRequest) -> MiddlewareOutcome + Send + Sync>;
+// Runs on the way out, after a response has been produced
+pub type ResponseMiddleware = Arc<dyn Fn(&HTTPRequest, HTTPResponse) -> HTTPResponse + Send + Sync>;
 
 #[derive(Clone)]
 pub struct Route {
-    method: String,
-    path: String,
+    method: Option<Method>,   // ← None matches any method (Router::any)
+    path: String,             // ← The pattern as registered, for describe
+    segments: Vec<Segment>,   // ← Parsed once from the path pattern
     handler: Handler,
-    middleware: Vec<Middleware>
+    middleware: Vec<Middleware>,
+    name: Option<String>      // ← Set with Router::name, looked up by url_for
+}
+
+// What router/server-level middleware decided. Middleware returning
+// Result<HTTPRequest, HTTPResponse> converts: Ok continues, Err responds.
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]  // ← Same layout as the Result it replaces
+pub enum MiddlewareOutcome {
+    Continue(HTTPRequest),  // ← Pass the (possibly modified) request on
+    Respond(HTTPResponse)   // ← Stop here and send this, e.g. a cache hit
+}
+
+impl From<Result<HTTPRequest, HTTPResponse>> for MiddlewareOutcome {
+    fn from(result: Result<HTTPRequest, HTTPResponse>) -> Self {
+        match result {
+            Ok(req) => Self::Continue(req),
+            Err(res) => Self::Respond(res),
+        }
+    }
+}
+
+// One registered route, for checking that registration matches intent.
+// See Router::describe and HTTPServer::describe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteInfo {
+    pub method: String,           // ← "*" for Router::any
+    pub path: String,             // ← Router prefixes included
+    pub host: Option<String>,     // ← Only for HTTPServer::add_router_for_host routers
+    pub name: Option<String>,     // ← From Router::name
+    pub middleware: Vec<String>,  // ← Server, then router middleware, in the order they run
+    pub route_middleware: usize   // ← The route's own middleware (plain fns, so unnamed), run last
+}
+
+// "GET /api/users/{id}  [api_key_check → admin_check] + 2 route middleware"
+impl fmt::Display for RouteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<7} {}", self.method, self.path)?;
+        if let Some(host) = &self.host {
+            write!(f, "  (Host: {})", host)?;
+        }
+        if !self.middleware.is_empty() {
+            write!(f, "  [{}]", self.middleware.join(" → "))?;
+        }
+        if self.route_middleware > 0 {
+            write!(f, " + {} route middleware", self.route_middleware)?;
+        }
+        Ok(())
+    }
+}
+
+// How a router treats "/about" vs "/about/"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrailingSlash {
+    Strict,     // ← Default: they're different paths
+    MatchBoth,  // ← Either form reaches the route
+    Redirect    // ← The other form redirects to the registered one
+}
+
+// One "/"-separated piece of a route pattern
+#[derive(Clone)]
+enum Segment {
+    Static(String),
+    Param { name: String, constraint: Option<Regex> },   // {id} or {id:\d+}
+    Wildcard(String)                                     // {*rest}
 }
 
 #[derive(Clone)]
 pub struct Router {
     prefix: String,
     routes: Vec<Route>,
-    middleware: Vec<Middleware>
+    middleware: Vec<SharedMiddleware>,
+    middleware_names: Vec<String>,  // ← Parallel to `middleware`, for describe
+    response_middleware: Vec<ResponseMiddleware>,
+    children: Vec<Router>,  // ← Mounted sub-routers, prefixes relative to ours
+    trailing_slash: TrailingSlash,
+    fallback: Option<Handler>  // ← Answers unmatched paths under our prefix
 }
 
 impl Route {
-    pub fn new(method: &str, path: &str, handler: Handler) -> Self {
+    pub fn new<F, R>(method: &str, path: &str, handler: F) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        Self::with_handler(method, path, sync_handler(handler))
+    }
+
+    // For handlers that need to .await (database calls, outbound HTTP, timers)
+    pub fn new_async<F, Fut, R>(method: &str, path: &str, handler: F) -> Self
+    where
+        F: Fn(HTTPRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+        R: IntoResponse,
+    {
+        Self::with_handler(method, path, Arc::new(move |req| {
+            let response = handler(req);
+            Box::pin(async move { response.await.into_response() })
+        }))
+    }
+
+    fn with_handler(method: &str, path: &str, handler: Handler) -> Self {
+        let segments: Vec<Segment> = path.split('/').map(|part| Segment::parse(part, path)).collect();
+        if segments[..segments.len() - 1].iter().any(|segment| matches!(segment, Segment::Wildcard(_))) {
+            panic!("Wildcard segment must be the last segment of the route: {}", path);
+        }
+
         Self {
-            method: method.to_string(),
+            method: Some(Method::from(method)),
             path: path.to_string(),
+            segments,
             handler,
-            middleware: Vec::new()
+            middleware: Vec::new(),
+            name: None
         }
     }
 
@@ -34,7 +147,12 @@ impl Route {
         self
     }
 
-    pub fn handle_request(&self, request: HTTPRequest) -> HTTPResponse {
+    fn any_method(mut self) -> Self {
+        self.method = None;
+        self
+    }
+
+    pub async fn handle_request(&self, request: HTTPRequest) -> HTTPResponse {
         let mut final_request: Result<HTTPRequest, HTTPResponse> = Ok(request.clone());
         for middleware in &self.middleware {
             final_request = match final_request {
@@ -43,34 +161,100 @@ impl Route {
             };
         }
         match final_request {
-            Ok(req) => (self.handler)(req),
+            Ok(req) => (self.handler)(req).await,
             Err(res) => res
         }
     }
 
+    // Fill the pattern's params, percent-encoded, removing each one it uses
+    // from `unused`
+    fn build_path(&self, unused: &mut Vec<(&str, &str)>) -> Result<String, String> {
+        let mut take = |name: &str| {
+            let position = unused.iter().position(|(key, _)| *key == name)
+                .ok_or_else(|| format!("Missing parameter '{}' for route '{}'", name, self.name.as_deref().unwrap_or_default()))?;
+            Ok::<_, String>(unused.remove(position).1)
+        };
+
+        let mut parts = Vec::with_capacity(self.segments.len());
+        for segment in &self.segments {
+            parts.push(match segment {
+                Segment::Static(part) => part.clone(),
+                Segment::Param { name, constraint } => {
+                    let value = take(name)?;
+                    if constraint.as_ref().is_some_and(|constraint| !constraint.is_match(value)) {
+                        return Err(format!("Parameter '{}' value {:?} doesn't match route '{}'", name, value, self.name.as_deref().unwrap_or_default()));
+                    }
+                    encode_path_segment(value)
+                }
+                // The rest of the path, so its slashes stay
+                Segment::Wildcard(name) => take(name)?.split('/').map(encode_path_segment).collect::<Vec<_>>().join("/"),
+            });
+        }
+
+        Ok(parts.join("/"))
+    }
+
+    // Compared left to right: at the first differing position a static
+    // segment beats a constrained param, which beats a plain param, which
+    // beats a wildcard
+    fn specificity(&self) -> Vec<u8> {
+        self.segments.iter().map(|segment| match segment {
+            Segment::Static(_) => 3,
+            Segment::Param { constraint: Some(_), .. } => 2,
+            Segment::Param { constraint: None, .. } => 1,
+            Segment::Wildcard(_) => 0,
+        }).collect()
+    }
+
     pub fn matches_route_pattern(&self, path: &str) -> bool {
-        let pattern_parts: Vec<&str> = self.path.split('/').collect();
         let path_parts: Vec<&str> = path.split('/').collect();
 
+        // A trailing {*name} swallows one or more remaining segments
+        if let Some(Segment::Wildcard(_)) = self.segments.last() {
+            let fixed = self.segments.len() - 1;
+            if path_parts.len() <= fixed || path_parts[fixed..].iter().all(|part| part.is_empty()) {
+                return false;
+            }
+            return segments_match(&self.segments[..fixed], &path_parts[..fixed]);
+        }
+
         // Must have same number of segments
-        if pattern_parts.len() != path_parts.len() {
+        if self.segments.len() != path_parts.len() {
             return false;
         }
 
-        // Compare each segment
-        for (pattern_part, path_part) in pattern_parts.iter().zip(path_parts.iter()) {
-            // Skip parameter placeholders like {id}
-            if pattern_part.starts_with('{') && pattern_part.ends_with('}') {
-                continue;
-            }
+        segments_match(&self.segments, &path_parts)
+    }
+}
 
-            // Exact match required for non-parameter segments
-            if pattern_part != path_part {
-                return false;
+impl Segment {
+    // `route` is only used for panic messages
+    fn parse(part: &str, route: &str) -> Self {
+        let Some(inner) = part.strip_prefix('{').and_then(|s| s.strip_suffix('}')) else {
+            return Segment::Static(part.to_string());
+        };
+
+        if let Some(name) = inner.strip_prefix('*') {
+            return Segment::Wildcard(name.to_string());
+        }
+
+        match inner.split_once(':') {
+            Some((name, pattern)) => {
+                // Anchored so the whole segment has to match
+                let constraint = Regex::new(&format!("^(?:{})$", pattern))
+                    .unwrap_or_else(|e| panic!("Invalid constraint in route {}: {}", route, e));
+                Segment::Param { name: name.to_string(), constraint: Some(constraint) }
             }
+            None => Segment::Param { name: inner.to_string(), constraint: None },
         }
+    }
 
-        true
+    fn matches(&self, path_part: &str) -> bool {
+        match self {
+            Segment::Static(expected) => expected == path_part,
+            Segment::Param { constraint: Some(constraint), .. } => constraint.is_match(&percent_decode(path_part)),
+            Segment::Param { constraint: None, .. } | Segment::Wildcard(_) => true,
+        }
     }
 }
 
@@ -79,53 +263,359 @@ impl Router {
         Self {
             prefix: prefix.to_string(),
             routes: Vec::new(),
-            middleware: Vec::new()
+            middleware: Vec::new(),
+            middleware_names: Vec::new(),
+            response_middleware: Vec::new(),
+            children: Vec::new(),
+            trailing_slash: TrailingSlash::Strict,
+            fallback: None
         }
     }
 
-    pub fn add_middleware(mut self, middleware: Middleware) -> Self {
-        self.middleware.push(middleware);
+    // `middleware` returns a MiddlewareOutcome or a Result<HTTPRequest, HTTPResponse>
+    pub fn add_middleware<F, R>(mut self, middleware: F) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: Into<MiddlewareOutcome>,
+    {
+        self.middleware_names.push(middleware_name::<F>());
+        self.middleware.push(Arc::new(move |req| middleware(req).into()));
         self
     }
 
-    pub fn get(mut self, path: &str, handler: Handler, middleware: Vec<Middleware>) -> Self {
-        let mut route = Route::new("GET", path, handler);
-        for middleware in middleware {
-            route = route.add_middleware(middleware);
-        }
-        self.routes.push(route);
+    // Applied to every response this router produces, in registration order
+    pub fn add_response_middleware<F>(mut self, middleware: F) -> Self
+    where
+        F: Fn(&HTTPRequest, HTTPResponse) -> HTTPResponse + Send + Sync + 'static,
+    {
+        self.response_middleware.push(Arc::new(middleware));
         self
     }
 
-    pub fn post(mut self, path: &str, handler: Handler, middleware: Vec<Middleware>) -> Self {
-        let mut route = Route::new("POST", path, handler);
-        for middleware in middleware {
-            route = route.add_middleware(middleware);
+    pub fn trailing_slash(mut self, mode: TrailingSlash) -> Self {
+        self.trailing_slash = mode;
+        self
+    }
+
+    // Handle any path under our prefix that no route or sub-router matched,
+    // instead of letting the next router try it. Our middleware still runs first.
+    pub fn fallback<F, R>(mut self, handler: F) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.fallback = Some(sync_handler(handler));
+        self
+    }
+
+    // Fold `other`'s routes and sub-routers into this router, for apps that
+    // build one prefix's routes in several functions. Its middleware is
+    // appended to ours (so it now covers every route), and its fallback is
+    // used only if we have none. Panics if the prefixes differ.
+    pub fn merge(mut self, other: Router) -> Self {
+        if self.prefix.trim_end_matches('/') != other.prefix.trim_end_matches('/') {
+            panic!("Cannot merge router '{}' into router '{}': prefixes differ", other.prefix, self.prefix);
         }
-        self.routes.push(route);
+
+        self.routes.extend(other.routes);
+        self.children.extend(other.children);
+        self.middleware.extend(other.middleware);
+        self.middleware_names.extend(other.middleware_names);
+        self.response_middleware.extend(other.response_middleware);
+        self.fallback = self.fallback.or(other.fallback);
         self
     }
 
-    pub fn put(mut self, path: &str, handler: Handler, middleware: Vec<Middleware>) -> Self {
-        let mut route = Route::new("PUT", path, handler);
-        for middleware in middleware {
-            route = route.add_middleware(middleware);
+    // Name the route added last, for url_for:
+    //   .get("/users/{id}", get_user, vec![]).name("user_detail")
+    pub fn name(mut self, name: &str) -> Self {
+        let Some(route) = self.routes.last_mut() else {
+            panic!("Router::name(\"{}\") called before any route was added", name);
+        };
+        route.name = Some(name.to_string());
+        self
+    }
+
+    // Path of a named route (searching mounted routers too) with `params`
+    // substituted. Params the pattern doesn't use become the query string:
+    //   url_for("user_detail", &[("id", "42"), ("tab", "posts")]) → "/users/42?tab=posts"
+    // Err if the name is unknown or a param is missing or fails its constraint.
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, String> {
+        let (prefix, route) = self.find_named(name).ok_or_else(|| format!("No route named '{}'", name))?;
+        let mut unused = params.to_vec();
+        let path = join_path(&prefix, &route.build_path(&mut unused)?);
+        if unused.is_empty() {
+            return Ok(path);
         }
-        self.routes.push(route);
+
+        let query = serde_urlencoded::to_string(&unused)
+            .map_err(|e| format!("Invalid query parameters for route '{}': {}", name, e))?;
+        Ok(format!("{}?{}", path, query))
+    }
+
+    // Every route under this router, sub-routers included, in the order
+    // they're tried
+    pub fn describe(&self) -> Vec<RouteInfo> {
+        self.describe_at("", &[])
+    }
+
+    fn describe_at(&self, parent_prefix: &str, inherited: &[String]) -> Vec<RouteInfo> {
+        let prefix = format!("{}{}", parent_prefix, self.prefix.trim_end_matches('/'));
+        let chain: Vec<String> = inherited.iter().chain(self.middleware_names.iter()).cloned().collect();
+
+        let own = self.routes.iter().map(|route| RouteInfo {
+            method: route.method.as_ref().map_or("*", Method::as_str).to_string(),
+            path: join_path(&prefix, &route.path),
+            host: None,
+            name: route.name.clone(),
+            middleware: chain.clone(),
+            route_middleware: route.middleware.len(),
+        });
+        let mounted = self.children.iter().flat_map(|child| child.describe_at(&prefix, &chain));
+        own.chain(mounted).collect()
+    }
+
+    // The named route and the prefixes of the routers leading to it
+    fn find_named(&self, name: &str) -> Option<(String, &Route)> {
+        let prefix = self.prefix.trim_end_matches('/');
+        if let Some(route) = self.routes.iter().find(|route| route.name.as_deref() == Some(name)) {
+            return Some((prefix.to_string(), route));
+        }
+        self.children.iter()
+            .find_map(|child| child.find_named(name))
+            .map(|(child_prefix, route)| (format!("{}{}", prefix, child_prefix), route))
+    }
+
+    // Nest `sub` under this router. Its prefix is relative to ours, and our
+    // middleware runs before its own.
+    pub fn mount(mut self, sub: Router) -> Self {
+        self.children.push(sub);
         self
     }
 
-    pub fn patch(mut self, path: &str, handler: Handler, middleware: Vec<Middleware>) -> Self {
-        let mut route = Route::new("PATCH", path, handler);
-        for middleware in middleware {
-            route = route.add_middleware(middleware);
+    pub fn get<F, R>(self, path: &str, handler: F, middleware: Vec<Middleware>) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.add_route("GET", path, handler, middleware)
+    }
+
+    pub fn post<F, R>(self, path: &str, handler: F, middleware: Vec<Middleware>) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.add_route("POST", path, handler, middleware)
+    }
+
+    pub fn put<F, R>(self, path: &str, handler: F, middleware: Vec<Middleware>) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.add_route("PUT", path, handler, middleware)
+    }
+
+    pub fn patch<F, R>(self, path: &str, handler: F, middleware: Vec<Middleware>) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.add_route("PATCH", path, handler, middleware)
+    }
+
+    pub fn delete<F, R>(self, path: &str, handler: F, middleware: Vec<Middleware>) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.add_route("DELETE", path, handler, middleware)
+    }
+
+    // Overrides the automatic OPTIONS response for this path
+    pub fn options<F, R>(self, path: &str, handler: F, middleware: Vec<Middleware>) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.add_route("OPTIONS", path, handler, middleware)
+    }
+
+    // Async variants: the handler returns a future, e.g. |req| async move { ... }
+    pub fn get_async<F, Fut, R>(self, path: &str, handler: F, middleware: Vec<Middleware>) -> Self
+    where
+        F: Fn(HTTPRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+        R: IntoResponse,
+    {
+        self.push_route(Route::new_async("GET", path, handler), middleware)
+    }
+
+    pub fn post_async<F, Fut, R>(self, path: &str, handler: F, middleware: Vec<Middleware>) -> Self
+    where
+        F: Fn(HTTPRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+        R: IntoResponse,
+    {
+        self.push_route(Route::new_async("POST", path, handler), middleware)
+    }
+
+    pub fn put_async<F, Fut, R>(self, path: &str, handler: F, middleware: Vec<Middleware>) -> Self
+    where
+        F: Fn(HTTPRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+        R: IntoResponse,
+    {
+        self.push_route(Route::new_async("PUT", path, handler), middleware)
+    }
+
+    pub fn patch_async<F, Fut, R>(self, path: &str, handler: F, middleware: Vec<Middleware>) -> Self
+    where
+        F: Fn(HTTPRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+        R: IntoResponse,
+    {
+        self.push_route(Route::new_async("PATCH", path, handler), middleware)
+    }
+
+    pub fn delete_async<F, Fut, R>(self, path: &str, handler: F, middleware: Vec<Middleware>) -> Self
+    where
+        F: Fn(HTTPRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+        R: IntoResponse,
+    {
+        self.push_route(Route::new_async("DELETE", path, handler), middleware)
+    }
+
+    // One handler for several methods, e.g. route(&["GET", "POST"], "/webhook", ...)
+    pub fn route<F, R>(mut self, methods: &[&str], path: &str, handler: F, middleware: Vec<Middleware>) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        let handler = sync_handler(handler);
+        for method in methods {
+            self = self.push_route(Route::with_handler(method, path, Arc::clone(&handler)), middleware.clone());
         }
-        self.routes.push(route);
         self
     }
 
-    pub fn delete(mut self, path: &str, handler: Handler, middleware: Vec<Middleware>) -> Self {
-        let mut route = Route::new("DELETE", path, handler);
+    // Matches every method. Routes registered for a specific method win on
+    // the same path.
+    pub fn any<F, R>(self, path: &str, handler: F, middleware: Vec<Middleware>) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.push_route(Route::new("*", path, handler).any_method(), middleware)
+    }
+
+    // Stack variants: the same as get/post/... but with a shared
+    // MiddlewareStack, e.g. .get_with("/admin", dashboard, &admin)
+    pub fn get_with<F, R>(self, path: &str, handler: F, stack: &MiddlewareStack) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.add_route("GET", path, handler, stack.clone().into())
+    }
+
+    pub fn post_with<F, R>(self, path: &str, handler: F, stack: &MiddlewareStack) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.add_route("POST", path, handler, stack.clone().into())
+    }
+
+    pub fn put_with<F, R>(self, path: &str, handler: F, stack: &MiddlewareStack) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.add_route("PUT", path, handler, stack.clone().into())
+    }
+
+    pub fn patch_with<F, R>(self, path: &str, handler: F, stack: &MiddlewareStack) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.add_route("PATCH", path, handler, stack.clone().into())
+    }
+
+    pub fn delete_with<F, R>(self, path: &str, handler: F, stack: &MiddlewareStack) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.add_route("DELETE", path, handler, stack.clone().into())
+    }
+
+    // WebSocket endpoint: a valid GET handshake gets 101 and the connection
+    // is handed to `handler`; anything else gets 426 Upgrade Required
+    pub fn ws<F, Fut>(self, path: &str, handler: F) -> Self
+    where
+        F: Fn(HTTPRequest, WebSocket) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler: WebSocketHandler = Arc::new(move |req, socket| Box::pin(handler(req, socket)));
+        self.push_route(Route::with_handler("GET", path, Arc::new(move |req| {
+            let response = websocket::upgrade(req, Arc::clone(&handler));
+            Box::pin(async move { response })
+        })), vec![])
+    }
+
+    // Serve files from `fs_root` for GET requests under `url_prefix`
+    pub fn static_dir(self, url_prefix: &str, fs_root: &str) -> Self {
+        let fs_root = fs_root.to_string();
+        let pattern = format!("{}/{{*path}}", url_prefix.trim_end_matches('/'));
+
+        self.get(&pattern, move |req: HTTPRequest| {
+            HTTPResponse::serve_file(&fs_root, &req.param("path", "")).with_range(&req)
+        }, vec![])
+    }
+
+    // Host a single-page app build: files from `dist_dir` under `url_prefix`,
+    // and dist_dir/index.html for every other path so client-side routing
+    // works. Paths that look like files (have an extension) still 404.
+    pub fn spa(self, url_prefix: &str, dist_dir: &str) -> Self {
+        let prefix = url_prefix.trim_end_matches('/');
+        let index = Path::new(dist_dir).join("index.html");
+        let serve_index = {
+            let index = index.clone();
+            move |req: HTTPRequest| HTTPResponse::file(&index).with_range(&req)
+        };
+
+        let mut router = self.get(&format!("{}/", prefix), serve_index.clone(), vec![]);
+        if !prefix.is_empty() {
+            router = router.get(prefix, serve_index, vec![]);
+        }
+
+        let dist_dir = dist_dir.to_string();
+        router.get(&format!("{}/{{*path}}", prefix), move |req: HTTPRequest| {
+            let path = req.param("path", "");
+            let response = HTTPResponse::serve_file(&dist_dir, &path);
+            let is_asset = path.rsplit('/').next().is_some_and(|name| name.contains('.'));
+
+            if response.status.code() == 404 && !is_asset {
+                HTTPResponse::file(&index).with_range(&req)
+            } else {
+                response.with_range(&req)
+            }
+        }, vec![])
+    }
+
+    fn add_route<F, R>(self, method: &str, path: &str, handler: F, middleware: Vec<Middleware>) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.push_route(Route::new(method, path, handler), middleware)
+    }
+
+    fn push_route(mut self, mut route: Route, middleware: Vec<Middleware>) -> Self {
         for middleware in middleware {
             route = route.add_middleware(middleware);
         }
@@ -133,50 +623,258 @@ impl Router {
         self
     }
 
-    fn inject_route_params_from_path(&self, request: &mut HTTPRequest, pattern: &str, actual_path: &str) {
+    fn inject_route_params_from_path(&self, request: &mut HTTPRequest, route: &Route, actual_path: &str) {
         let path_parts: Vec<&str> = actual_path.split('/').collect();
-        let pattern_parts: Vec<&str> = pattern.split('/').collect();
 
-        for (pattern_part, path_part) in pattern_parts.iter().zip(path_parts.iter()) {
-            if let Some(param_name) = pattern_part.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
-                request.route_params.insert(param_name.to_string(), path_part.to_string());
+        for (i, segment) in route.segments.iter().enumerate() {
+            match segment {
+                Segment::Param { name, .. } => {
+                    if let Some(path_part) = path_parts.get(i) {
+                        request.route_params.insert(name.clone(), percent_decode(path_part));
+                    }
+                }
+                Segment::Wildcard(name) => {
+                    // Wildcards capture the rest of the path, slashes included
+                    let rest = path_parts.get(i..).unwrap_or_default().join("/");
+                    request.route_params.insert(name.clone(), percent_decode(&rest));
+                }
+                Segment::Static(_) => {}
             }
         }
     }
 
-    pub fn handle_request(&self, mut request: HTTPRequest) -> HTTPResponse {
+    fn run_middleware(chain: &[SharedMiddleware], request: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
+        let mut processed_request = request;
+        for middleware in chain {
+            processed_request = match (middleware)(processed_request) {
+                MiddlewareOutcome::Continue(req) => req,
+                MiddlewareOutcome::Respond(res) => return Err(res),
+            };
+        }
+        Ok(processed_request)
+    }
+
+    pub async fn handle_request(&self, request: HTTPRequest) -> HTTPResponse {
+        self.try_handle(request).await
+            .unwrap_or_else(|| HTTPResponse::not_found("No matching route found").generated())
+    }
+
+    // None means no route pattern matched, so another router may take it.
+    // A 404 returned by a handler comes back as Some and is kept.
+    pub async fn try_handle(&self, request: HTTPRequest) -> Option<HTTPResponse> {
         let full_path = request.route.clone();
+        self.try_handle_at(request, &full_path, &[]).await
+    }
 
+    // `path` is what's left after the parent routers' prefixes, and
+    // `inherited` is their middleware, outermost first
+    // (boxed because mounted routers make this recursive)
+    fn try_handle_at<'a>(&'a self, request: HTTPRequest, path: &'a str, inherited: &'a [SharedMiddleware]) -> Pin<Box<dyn Future<Output = Option<HTTPResponse>> + Send + 'a>> {
+        Box::pin(async move {
+            if self.response_middleware.is_empty() {
+                return self.route_at(request, path, inherited).await;
+            }
+
+            // Response middleware gets to see the request that produced the response
+            let original = request.clone();
+            let response = self.route_at(request, path, inherited).await?;
+            Some(self.response_middleware.iter().fold(response, |res, middleware| (middleware)(&original, res)))
+        })
+    }
+
+    async fn route_at(&self, request: HTTPRequest, path: &str, inherited: &[SharedMiddleware]) -> Option<HTTPResponse> {
         // Strip prefix to get relative path
-        let relative_path = if self.prefix == "/" {
-            full_path.clone()
-        } else {
-            match full_path.strip_prefix(&self.prefix) {
-                Some(p) => p.to_string(),
-                None => return HTTPResponse::not_found("Route prefix not matched"),
+        let relative_path = strip_path_prefix(path, &self.prefix)?.to_string();
+        let chain: Vec<SharedMiddleware> = inherited.iter()
+            .chain(self.middleware.iter())
+            .cloned()
+            .collect();
+
+        let (matched_route, mut allowed_methods) = self.find_route(&request.method, &relative_path);
+        if let Some(route) = matched_route {
+            return Some(self.run_route(route, request, &relative_path, &chain).await);
+        }
+
+        // Our own routes didn't match, so try the mounted sub-routers
+        for child in &self.children {
+            if let Some(res) = child.try_handle_at(request.clone(), &relative_path, &chain).await {
+                return Some(res);
             }
-        };
+        }
+
+        // Retry with the trailing slash added/removed, unless we're strict about it
+        if allowed_methods.is_empty()
+            && self.trailing_slash != TrailingSlash::Strict
+            && let Some(alternate_path) = toggle_trailing_slash(&relative_path)
+        {
+            let (alternate_route, alternate_methods) = self.find_route(&request.method, &alternate_path);
+
+            if self.trailing_slash == TrailingSlash::Redirect && (alternate_route.is_some() || !alternate_methods.is_empty()) {
+                return Some(trailing_slash_redirect(&request));
+            }
+            if let Some(route) = alternate_route {
+                return Some(self.run_route(route, request, &alternate_path, &chain).await);
+            }
+            allowed_methods = alternate_methods;
+        }
+
+        // No explicit OPTIONS route, so advertise what the path supports
+        if request.method == Method::Options && !allowed_methods.is_empty() {
+            return Some(HTTPResponse::new(204, "")
+                .with_header("Allow", &allowed_methods.join(", ")));
+        }
+
+        // Path exists but not for this method
+        if !allowed_methods.is_empty() {
+            return Some(HTTPResponse::new(405, "Method Not Allowed")
+                .with_header("Allow", &allowed_methods.join(", "))
+                .generated());
+        }
+
+        let fallback = self.fallback.as_ref()?;
+        match Self::run_middleware(&chain, request) {
+            Ok(req) => Some(fallback(req).await),
+            Err(res) => Some(res)
+        }
+    }
 
-        // Find matching route
+    // Most specific route for this method and path, plus the other methods
+    // registered for the path (for Allow headers)
+    fn find_route(&self, method: &Method, relative_path: &str) -> (Option<&Route>, Vec<&str>) {
+        let mut allowed_methods: Vec<&str> = Vec::new();
+        let mut matched_route: Option<&Route> = None;
+        let mut get_route: Option<&Route> = None;
+        let mut any_route: Option<&Route> = None;
         for route in &self.routes {
-            if request.method == route.method && route.matches_route_pattern(&relative_path) {
-                // CRITICAL FIX: Pass relative_path, not request.route!
-                self.inject_route_params_from_path(&mut request, &route.path, &relative_path);
-
-                let mut processed_request: Result<HTTPRequest, HTTPResponse> = Ok(request.clone());
-                for middleware in &self.middleware {
-                    processed_request = match processed_request {
-                        Ok(req) => (middleware)(req),
-                        Err(res) => return res
-                    }
-                }
-                return match processed_request {
-                    Ok(req) => route.handle_request(req),
-                    Err(res) => res
-                }
+            if !route.matches_route_pattern(relative_path) {
+                continue;
+            }
+            let Some(route_method) = &route.method else {
+                keep_most_specific(&mut any_route, route);
+                continue;
+            };
+            if method == route_method {
+                keep_most_specific(&mut matched_route, route);
+                continue;
+            }
+            // HEAD is answered by the GET handler unless a HEAD route exists
+            if *method == Method::Head && *route_method == Method::Get {
+                keep_most_specific(&mut get_route, route);
+                continue;
+            }
+            if !allowed_methods.contains(&route_method.as_str()) {
+                allowed_methods.push(route_method.as_str());
             }
         }
 
-        HTTPResponse::not_found("No matching route found")
+        // A catch-all-methods route only beats a method match by being more specific
+        let method_route = matched_route.or(get_route);
+        let route = match (method_route, any_route) {
+            (Some(exact), Some(any)) if any.specificity() > exact.specificity() => Some(any),
+            (None, any) => any,
+            (exact, _) => exact,
+        };
+        (route, allowed_methods)
+    }
+
+    async fn run_route(&self, route: &Route, mut request: HTTPRequest, relative_path: &str, chain: &[SharedMiddleware]) -> HTTPResponse {
+        // CRITICAL FIX: Pass relative_path, not request.route!
+        self.inject_route_params_from_path(&mut request, route, relative_path);
+
+        match Self::run_middleware(chain, request) {
+            Ok(req) => route.handle_request(req).await,
+            Err(res) => res
+        }
+    }
+}
+
+// Wrap a plain handler so it fits the async Handler type
+pub(crate) fn sync_handler<F, R>(handler: F) -> Handler
+where
+    F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+    R: IntoResponse,
+{
+    Arc::new(move |req| {
+        let response = handler(req).into_response();
+        Box::pin(async move { response })
+    })
+}
+
+// Strip `prefix` only on a segment boundary, so "/api" claims "/api" and
+// "/api/users" but not "/apiv2". An exact match leaves "/".
+fn strip_path_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    let prefix = prefix.trim_end_matches('/');
+    if prefix.is_empty() {
+        return Some(path);
+    }
+
+    match path.strip_prefix(prefix) {
+        Some("") => Some("/"),
+        Some(rest) if rest.starts_with('/') => Some(rest),
+        _ => None,
+    }
+}
+
+// A route path under its routers' (already trimmed) prefixes. A "/" route
+// under a prefix is served at the bare prefix.
+fn join_path(prefix: &str, path: &str) -> String {
+    match path {
+        "/" if !prefix.is_empty() => prefix.to_string(),
+        _ => format!("{}{}", prefix, path),
+    }
+}
+
+// Readable name for a middleware type: "my_app::auth::api_key_check" →
+// "api_key_check", and a closure is named after the function that built it,
+// e.g. "preflight (closure)" for Cors::preflight
+pub(crate) fn middleware_name<F>() -> String {
+    let full_name = std::any::type_name::<F>();
+    let name = full_name.split("::").filter(|segment| *segment != "{{closure}}").last().unwrap_or(full_name);
+    if full_name.ends_with("{{closure}}") {
+        format!("{} (closure)", name)
+    } else {
+        name.to_string()
     }
-}
\ No newline at end of file
+}
+
+// Everything but RFC 3986 unreserved characters is %XX-escaped
+fn encode_path_segment(value: &str) -> String {
+    value.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+        _ => format!("%{:02X}", byte),
+    }).collect()
+}
+
+// "/about" <-> "/about/". The root has no alternate form.
+fn toggle_trailing_slash(path: &str) -> Option<String> {
+    match path.strip_suffix('/') {
+        Some("") => None,
+        Some(trimmed) => Some(trimmed.to_string()),
+        None => Some(format!("{}/", path)),
+    }
+}
+
+// 301 for GET/HEAD; 308 otherwise so the method and body are kept
+fn trailing_slash_redirect(request: &HTTPRequest) -> HTTPResponse {
+    let mut location = toggle_trailing_slash(&request.route).unwrap_or_else(|| "/".to_string());
+    if !request.query_string.is_empty() {
+        location = format!("{}?{}", location, request.query_string);
+    }
+
+    match request.method {
+        Method::Get | Method::Head => HTTPResponse::redirect_permanent(&location),
+        _ => HTTPResponse::redirect_with_status(308, &location),
+    }
+}
+
+// Ties go to whichever route was registered first
+fn keep_most_specific<'a>(best: &mut Option<&'a Route>, candidate: &'a Route) {
+    if best.is_none_or(|current| candidate.specificity() > current.specificity()) {
+        *best = Some(candidate);
+    }
+}
+
+// Every pattern segment has to accept the path segment at the same position
+fn segments_match(segments: &[Segment], path_parts: &[&str]) -> bool {
+    segments.iter().zip(path_parts.iter()).all(|(segment, path_part)| segment.matches(path_part))
+}
@@ -0,0 +1,32 @@
+use std::hint::black_box;
+use ring::hmac;
+
+// Compare secrets (API keys, tokens, signatures) without leaking where they differ.
+// Every byte is examined, so the time taken doesn't depend on the position of the
+// first mismatch. Only the length can be learned, and that's not the secret part.
+pub fn constant_time_eq(a: impl AsRef<[u8]>, b: impl AsRef<[u8]>) -> bool {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    if a.len() != b.len() {
+        return false;
+    }
+
+    // OR together every byte difference; black_box keeps the compiler from
+    // turning this back into an early-exit loop
+    let difference = a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| black_box(acc | (x ^ y)));
+    difference == 0
+}
+
+// HMAC-SHA256 (RFC 2104), e.g. for signing HS256 tokens
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), message);
+    let mut signature = [0; 32];
+    signature.copy_from_slice(tag.as_ref());
+    signature
+}
+
+// Check an HMAC-SHA256 signature in constant time
+pub fn verify_hmac_sha256(key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    hmac::verify(&hmac::Key::new(hmac::HMAC_SHA256, key), message, signature).is_ok()
+}
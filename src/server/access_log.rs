@@ -0,0 +1,37 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::requests::Method;
+
+// Called once per request, after the response has been written
+pub type AccessLogger = Arc<dyn Fn(&AccessLogEntry) + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub struct AccessLogEntry {
+    pub method: Method,
+    pub path: String,             // ← Includes the query string
+    pub version: String,
+    pub status: u16,
+    pub body_size: usize,         // ← Bytes of body sent (chunk payloads for streams)
+    pub elapsed: Duration,        // ← From parsed request to last byte written
+    pub remote_addr: Option<SocketAddr>
+}
+
+// Default format: 127.0.0.1:52144 "GET /about HTTP/1.1" 200 512B 1.24ms
+impl fmt::Display for AccessLogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let remote = self.remote_addr.map_or_else(|| "-".to_string(), |addr| addr.to_string());
+        write!(
+            f,
+            "{} \"{} {} {}\" {} {}B {:.2}ms",
+            remote,
+            self.method,
+            self.path,
+            self.version,
+            self.status,
+            self.body_size,
+            self.elapsed.as_secs_f64() * 1000.0
+        )
+    }
+}
@@ -0,0 +1,62 @@
+// De-chunking for request bodies sent with Transfer-Encoding: chunked
+
+pub(crate) enum ChunkedBody {
+    Complete { body: Vec<u8>, end: usize },  // ← `end` is just past the terminator
+    Incomplete,                              // ← Need more bytes off the socket
+    TooLarge
+}
+
+// Decode the chunked body starting at `start` in `buffer`.
+// Each chunk is "<hex size>[;ext]\r\n<data>\r\n"; a 0 size ends the body,
+// followed by optional trailer lines and a blank line.
+pub(crate) fn decode_chunked(buffer: &[u8], start: usize, max_body_size: usize) -> Result<ChunkedBody, String> {
+    let mut body = Vec::new();
+    let mut pos = start;
+
+    loop {
+        let Some(line_end) = find_crlf(buffer, pos) else {
+            return Ok(ChunkedBody::Incomplete);
+        };
+        let size_line = String::from_utf8_lossy(&buffer[pos..line_end]);
+        let size_text = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_text, 16)
+            .map_err(|_| format!("Invalid chunk size: {}", size_text))?;
+        pos = line_end + 2;
+
+        if size == 0 {
+            break;
+        }
+        if size > max_body_size - body.len() {
+            return Ok(ChunkedBody::TooLarge);
+        }
+
+        let data_end = pos + size;
+        if buffer.len() < data_end + 2 {
+            return Ok(ChunkedBody::Incomplete);
+        }
+        if &buffer[data_end..data_end + 2] != b"\r\n" {
+            return Err("Chunk data not followed by CRLF".to_string());
+        }
+        body.extend_from_slice(&buffer[pos..data_end]);
+        pos = data_end + 2;
+    }
+
+    // Skip trailer headers up to the blank line
+    loop {
+        let Some(line_end) = find_crlf(buffer, pos) else {
+            return Ok(ChunkedBody::Incomplete);
+        };
+        let is_blank = line_end == pos;
+        pos = line_end + 2;
+        if is_blank {
+            return Ok(ChunkedBody::Complete { body, end: pos });
+        }
+    }
+}
+
+fn find_crlf(buffer: &[u8], from: usize) -> Option<usize> {
+    buffer.get(from..)?
+        .windows(2)
+        .position(|window| window == b"\r\n")
+        .map(|pos| pos + from)
+}
@@ -1,2 +1,7 @@
+#[allow(clippy::module_inception)]
 pub mod server;
-pub use server::*;
\ No newline at end of file
+mod tls;
+mod chunked;
+pub mod access_log;
+pub use server::*;
+pub use access_log::*;
\ No newline at end of file
@@ -1,14 +1,74 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
-use crate::requests::HTTPRequest;
-use crate::responses::HTTPResponse;
-use crate::routing::{Router, Middleware};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, watch};
+use tokio::task::JoinSet;
+use tokio::time::{Instant, timeout, timeout_at};
+use socket2::{SockRef, TcpKeepalive};
+use tokio_rustls::TlsAcceptor;
+use crate::requests::{HTTPRequest, Method};
+use crate::responses::{CompressionOptions, HTTPResponse, IntoResponse};
+use crate::responses::chunked::write_chunks;
+use crate::responses::file::write_file;
+use crate::routing::{Handler, MiddlewareOutcome, Router, RouteInfo, SharedMiddleware, ResponseMiddleware, middleware_name, sync_handler};
+use crate::server::tls;
+use crate::server::access_log::{AccessLogEntry, AccessLogger};
+use crate::server::chunked::{ChunkedBody, decode_chunked};
+use crate::websocket::{OnUpgrade, WebSocket};
+
+// Builds the body for one of the server's own errors from its status and message
+type ErrorPage = Arc<dyn Fn(u16, &str) -> HTTPResponse + Send + Sync>;
+
+const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024; // 1MB
+const DEFAULT_MAX_HEADER_SIZE: usize = 8 * 1024; // 8KB
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub struct HTTPServer {
     addr: String,
     routers: Vec<Router>,
-    middleware: Vec<Middleware>
+    host_routers: Vec<(String, Router)>,  // ← Tried first, only for a matching Host
+    middleware: Vec<SharedMiddleware>,
+    middleware_names: Vec<String>,  // ← Parallel to `middleware`, for describe
+    response_middleware: Vec<ResponseMiddleware>,
+    default_headers: Vec<(String, String)>,  // ← Added to responses that don't set them
+    max_body_size: usize,
+    max_header_size: usize,
+    read_timeout: Duration,
+    compression: Option<CompressionOptions>,  // ← None when compression is off
+    fallback: Option<Handler>,  // ← Used when no router claims the request
+    health_paths: Vec<String>,  // ← Liveness probes, answered before any middleware
+    error_pages: Vec<(RangeInclusive<u16>, ErrorPage)>,  // ← First matching range wins
+    access_log: Option<AccessLogger>,
+    connection_limit: Option<Arc<Semaphore>>,  // ← One permit per open connection
+    worker_threads: Option<usize>,  // ← For run_blocking; None uses Tokio's default (one per core)
+    tcp_nodelay: bool,
+    tcp_keepalive: Option<Duration>,  // ← Idle time before the OS starts keepalive probes
+    shutdown_timeout: Option<Duration>,  // ← Grace period for in-flight requests; None waits for all
+    max_requests_per_connection: Option<usize>  // ← None lets keep-alive connections serve forever
+}
+
+// A server whose listener is already bound, so the real address is known
+// before serving starts (port 0 resolves to an ephemeral port)
+pub struct BoundServer {
+    server: HTTPServer,
+    listener: TcpListener,
+    local_addr: SocketAddr
+}
+
+// Outcome of reading one request off the connection
+enum ReadResult {
+    Request(Vec<u8>),
+    Reject(HTTPResponse),
+    Closed
 }
 
 impl HTTPServer {
@@ -16,12 +76,45 @@ impl HTTPServer {
         Self {
             addr: addr.to_string(),
             routers: Vec::new(),
-            middleware: Vec::new()
+            host_routers: Vec::new(),
+            middleware: Vec::new(),
+            middleware_names: Vec::new(),
+            response_middleware: Vec::new(),
+            default_headers: Vec::new(),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            compression: None,
+            fallback: None,
+            health_paths: Vec::new(),
+            error_pages: Vec::new(),
+            access_log: None,
+            connection_limit: None,
+            worker_threads: None,
+            tcp_nodelay: false,
+            tcp_keepalive: None,
+            shutdown_timeout: None,
+            max_requests_per_connection: None
         }
     }
 
-    pub fn add_middleware(mut self, middleware: Middleware) -> Self {
-        self.middleware.push(middleware);
+    // `middleware` returns a MiddlewareOutcome or a Result<HTTPRequest, HTTPResponse>
+    pub fn add_middleware<F, R>(mut self, middleware: F) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: Into<MiddlewareOutcome>,
+    {
+        self.middleware_names.push(middleware_name::<F>());
+        self.middleware.push(Arc::new(move |req| middleware(req).into()));
+        self
+    }
+
+    // Runs on every dispatched response, after any router-level ones
+    pub fn add_response_middleware<F>(mut self, middleware: F) -> Self
+    where
+        F: Fn(&HTTPRequest, HTTPResponse) -> HTTPResponse + Send + Sync + 'static,
+    {
+        self.response_middleware.push(Arc::new(middleware));
         self
     }
 
@@ -30,87 +123,838 @@ impl HTTPServer {
         self
     }
 
-    async fn handle_connection(
-        mut stream: TcpStream,
-        routers: Arc<Vec<Router>>,
-        middleware: Arc<Vec<Middleware>>
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        loop {
-            let mut buffer = [0; 4096];
-            let n_bytes = stream.read(&mut buffer).await?;
+    // Answer requests no router matched (branded 404 page, SPA index.html, ...)
+    pub fn fallback<F, R>(mut self, handler: F) -> Self
+    where
+        F: Fn(HTTPRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.fallback = Some(sync_handler(handler));
+        self
+    }
+
+    // Log every request once its response is sent, in the default format
+    pub fn access_log(self) -> Self {
+        self.access_log_with(|entry| println!("{}", entry))
+    }
+
+    // Log with your own format or destination
+    pub fn access_log_with<F>(mut self, logger: F) -> Self
+    where
+        F: Fn(&AccessLogEntry) + Send + Sync + 'static,
+    {
+        self.access_log = Some(Arc::new(logger));
+        self
+    }
+
+    // Every route in the order requests try them: health endpoints, host
+    // routers, then the rest. Each lists the server and router middleware
+    // in front of it, e.g. for a debug dump at startup:
+    //   for route in server.describe() { println!("{}", route); }
+    pub fn describe(&self) -> Vec<RouteInfo> {
+        let health = self.health_paths.iter().map(|path| RouteInfo {
+            method: "GET".to_string(),
+            path: path.clone(),
+            host: None,
+            name: None,
+            middleware: Vec::new(),  // ← Answered before any middleware
+            route_middleware: 0,
+        });
+        let host_routes = self.host_routers.iter()
+            .flat_map(|(host, router)| router.describe().into_iter().map(|info| RouteInfo { host: Some(host.clone()), ..info }));
+        let routes = self.routers.iter().flat_map(Router::describe);
+
+        let mut described: Vec<RouteInfo> = health.collect();
+        for mut info in host_routes.chain(routes) {
+            info.middleware.splice(0..0, self.middleware_names.iter().cloned());
+            described.push(info);
+        }
+        described
+    }
+
+    // Serve `router` only for requests whose Host is `host` ("api.example.com",
+    // or "api.example.com:8080" to also match the port). Host routers are tried
+    // before the plain ones, so their prefixes may overlap.
+    pub fn add_router_for_host(mut self, host: &str, router: Router) -> Self {
+        self.host_routers.push((host.to_ascii_lowercase(), router));
+        self
+    }
+
+    // Requests with a larger body are rejected with 413 Payload Too Large
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+
+    // Request line + headers larger than this are rejected with 431
+    pub fn max_header_size(mut self, bytes: usize) -> Self {
+        self.max_header_size = bytes;
+        self
+    }
+
+    // Handle at most `limit` connections at once. Further clients aren't
+    // accepted until one closes, so they wait in the listen backlog.
+    pub fn max_connections(mut self, limit: usize) -> Self {
+        self.connection_limit = Some(Arc::new(Semaphore::new(limit)));
+        self
+    }
 
-            if n_bytes == 0 {
-                break; // Client disconnected
+    // How long a client gets to send a full request before the connection is dropped
+    pub fn read_timeout(mut self, duration: Duration) -> Self {
+        self.read_timeout = duration;
+        self
+    }
+
+    // Sent on every response (errors included) unless it already has `name`,
+    // e.g. .default_header("X-Frame-Options", "DENY")
+    pub fn default_header(mut self, name: &str, value: &str) -> Self {
+        self.default_headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    // Liveness probe, e.g. .health_endpoint("/healthz"): GET/HEAD always get
+    // 200 "OK", skipping all middleware (auth, maintenance mode, ...) so the
+    // probe only checks the process is up and serving
+    pub fn health_endpoint(mut self, path: &str) -> Self {
+        self.health_paths.push(path.to_string());
+        self
+    }
+
+    // Restyle the errors the server produces itself - unmatched paths (404),
+    // wrong methods (405), panics (500) and rejected requests (400, 408, 413,
+    // 431) - when their status is in `codes`. Errors handlers return are
+    // left alone. The status and headers like Allow are kept:
+    //   .error_page(400..=599, |code, message| HTTPResponse::error_json(code, message))
+    pub fn error_page<F, R>(mut self, codes: RangeInclusive<u16>, template: F) -> Self
+    where
+        F: Fn(u16, &str) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.error_pages.push((codes, Arc::new(move |code, message| template(code, message).into_response())));
+        self
+    }
+
+    // Disable Nagle's algorithm on accepted TCP connections, so small writes
+    // (headers, then a short body) go out at once instead of being delayed
+    pub fn tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.tcp_nodelay = enabled;
+        self
+    }
+
+    // Have the OS probe connections idle for `idle`, so dead peers on
+    // long-lived keep-alive connections are noticed
+    pub fn tcp_keepalive(mut self, idle: Duration) -> Self {
+        self.tcp_keepalive = Some(idle);
+        self
+    }
+
+    // Apply tcp_nodelay/tcp_keepalive to a connection. The server does this
+    // for every connection it accepts.
+    pub fn apply_socket_options(&self, stream: &TcpStream) -> std::io::Result<()> {
+        if self.tcp_nodelay {
+            stream.set_nodelay(true)?;
+        }
+        if let Some(idle) = self.tcp_keepalive {
+            SockRef::from(stream).set_tcp_keepalive(&TcpKeepalive::new().with_time(idle))?;
+        }
+        Ok(())
+    }
+
+    // On shutdown, idle keep-alive connections close at once and requests
+    // already being handled get this long to finish before their
+    // connections are dropped. Without it, shutdown waits for all of them.
+    pub fn shutdown_timeout(mut self, grace: Duration) -> Self {
+        self.shutdown_timeout = Some(grace);
+        self
+    }
+
+    // Close a keep-alive connection after it has served `limit` requests
+    // (the last response says Connection: close), so one client can't hold
+    // a slot under max_connections forever. 0 is treated as 1: the request
+    // that opened the connection is always answered.
+    pub fn max_requests_per_connection(mut self, limit: usize) -> Self {
+        self.max_requests_per_connection = Some(limit);
+        self
+    }
+
+    // Runtime threads used by run_blocking. Has no effect on run(), which
+    // uses whatever runtime it's awaited on. Panics on 0.
+    pub fn workers(mut self, threads: usize) -> Self {
+        if threads == 0 {
+            panic!("HTTPServer::workers needs at least one thread");
+        }
+        self.worker_threads = Some(threads);
+        self
+    }
+
+    // Gzip responses for clients that send Accept-Encoding: gzip
+    pub fn enable_compression(mut self) -> Self {
+        self.compression.get_or_insert_with(CompressionOptions::default);
+        self
+    }
+
+    // Don't gzip bodies smaller than `bytes` (default 256). Turns compression on.
+    pub fn compression_min_size(mut self, bytes: usize) -> Self {
+        self.compression = Some(self.compression.take().unwrap_or_default().min_size(bytes));
+        self
+    }
+
+    // Only gzip these Content-Types, e.g. ["text/*", "application/json"].
+    // By default anything but images, video, audio and archives is.
+    // Turns compression on.
+    pub fn compressible_types<'a>(mut self, types: impl IntoIterator<Item = &'a str>) -> Self {
+        self.compression = Some(self.compression.take().unwrap_or_default().compressible_types(types));
+        self
+    }
+
+    // Response middleware sees the request as server middleware left it
+    // (e.g. with a RequestId extension), or as it arrived if one responded early
+    async fn dispatch(&self, request: HTTPRequest) -> HTTPResponse {
+        let is_probe = matches!(request.method, Method::Get | Method::Head);
+        if is_probe && self.health_paths.contains(&request.route) {
+            return HTTPResponse::ok("OK").with_header("Cache-Control", "no-store");
+        }
+
+        if self.response_middleware.is_empty() {
+            return match self.run_middleware(request) {
+                MiddlewareOutcome::Continue(req) => self.route(req).await,
+                MiddlewareOutcome::Respond(res) => res,
+            };
+        }
+
+        let original = request.clone();
+        let (seen, response) = match self.run_middleware(request) {
+            MiddlewareOutcome::Continue(req) => (req.clone(), self.route(req).await),
+            MiddlewareOutcome::Respond(res) => (original, res),
+        };
+        self.response_middleware.iter().fold(response, |res, middleware| (middleware)(&seen, res))
+    }
+
+    // `res` run through the error_page registered for its status, if any
+    fn error_page_for(&self, res: HTTPResponse) -> HTTPResponse {
+        let code = res.status.code();
+        let Some((_, template)) = self.error_pages.iter().find(|(codes, _)| codes.contains(&code)) else {
+            return res;
+        };
+
+        let mut page = template(code, &String::from_utf8_lossy(res.body()));
+        page.status = res.status;
+        for (name, value) in res.headers {
+            if !name.eq_ignore_ascii_case("Content-Type") && page.get_header(&name).is_none() {
+                page.headers.push((name, value));
             }
+        }
+        page
+    }
 
-            let request_str = String::from_utf8_lossy(&buffer[..n_bytes]);
+    fn add_default_headers(&self, res: &mut HTTPResponse) {
+        for (name, value) in &self.default_headers {
+            if res.get_header(name).is_none() {
+                res.set_header(name, value);
+            }
+        }
+    }
 
-            // Parse request
-            let request = match HTTPRequest::new(&request_str) {
-                Ok(req) => req,
-                Err(e) => {
-                    let res = HTTPResponse::new(400, &format!("Bad Request: {}", e));
-                    stream.write_all(res.to_http_string().as_bytes()).await?;
-                    continue;
-                }
+    // handle global middleware chain
+    fn run_middleware(&self, request: HTTPRequest) -> MiddlewareOutcome {
+        let mut request_to_route = request;
+        for middleware in self.middleware.iter() {
+            request_to_route = match (middleware)(request_to_route) {
+                MiddlewareOutcome::Continue(req) => req,
+                respond => return respond
             };
+        }
+        MiddlewareOutcome::Continue(request_to_route)
+    }
+
+    async fn route(&self, request_to_route: HTTPRequest) -> HTTPResponse {
+
+        // Routers bound to this Host first, then the rest until one handles it
+        let host = request_to_route.host().map(|host| host.to_ascii_lowercase());
+        let hostname = request_to_route.host_without_port().map(|host| host.to_ascii_lowercase());
+        let host_matched = self.host_routers.iter()
+            .filter(|(router_host, _)| Some(router_host) == host.as_ref() || Some(router_host) == hostname.as_ref())
+            .map(|(_, router)| router);
+
+        for router in host_matched.chain(self.routers.iter()) {
+            if let Some(res) = router.try_handle(request_to_route.clone()).await {
+                return if res.is_generated() { self.error_page_for(res) } else { res };
+            }
+        }
+
+        match &self.fallback {
+            Some(fallback) => fallback(request_to_route).await,
+            None => self.error_page_for(HTTPResponse::not_found("No router matched this path")),
+        }
+    }
+
+    // Reads one full request (header block + Content-Length body) into `buffer`.
+    // Bytes past the end of the request are left in `buffer` for the next one.
+    async fn read_request<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: &mut S, buffer: &mut Vec<u8>) -> std::io::Result<ReadResult> {
+        // One deadline for the whole request, so trickling bytes doesn't reset it
+        let deadline = Instant::now() + self.read_timeout;
+
+        let header_end = loop {
+            if let Some(pos) = find_header_end(buffer) {
+                break pos;
+            }
+            // Give up as soon as the unfinished header block is already too big
+            if buffer.len() > self.max_header_size {
+                return Ok(ReadResult::Reject(header_too_large()));
+            }
+            if let Some(stop) = Self::read_more(stream, buffer, deadline).await? {
+                return Ok(stop);
+            }
+        };
+
+        if header_end > self.max_header_size {
+            return Ok(ReadResult::Reject(header_too_large()));
+        }
+
+        // Whichever of Transfer-Encoding: chunked and Content-Length is sent
+        // frames the body (neither means no body). Both at once is how request
+        // smuggling works - a proxy may pick the other one - so it's refused.
+        let header_block = &buffer[..header_end];
+        if find_header(header_block, "Transfer-Encoding").is_some() && find_header(header_block, "Content-Length").is_some() {
+            return Ok(ReadResult::Reject(HTTPResponse::new(400, "Bad Request: both Content-Length and Transfer-Encoding were sent")));
+        }
+        let chunked = match is_chunked(header_block) {
+            Ok(chunked) => chunked,
+            Err(res) => return Ok(ReadResult::Reject(res)),
+        };
+        let content_length = match parse_content_length(header_block) {
+            Ok(length) => length,
+            Err(e) => return Ok(ReadResult::Reject(HTTPResponse::new(400, &format!("Bad Request: {}", e)))),
+        };
+
+        // Refuse before reading the body so huge uploads never get buffered
+        if content_length > self.max_body_size {
+            return Ok(ReadResult::Reject(HTTPResponse::new(413, "Payload Too Large")));
+        }
+
+        // The client is holding the body back until we say go ahead
+        let body_pending = chunked || content_length > 0;
+        if body_pending && buffer.len() == header_end && expects_continue(&buffer[..header_end]) {
+            stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await?;
+            stream.flush().await?;
+        }
+
+        if chunked {
+            return self.read_chunked_body(stream, buffer, header_end, deadline).await;
+        }
+
+        // A body that stops short of Content-Length is refused rather than
+        // handled truncated. Bytes past it belong to the next request and
+        // get parsed (or rejected) as one.
+        let request_end = header_end + content_length;
+        while buffer.len() < request_end {
+            if Self::read_more(stream, buffer, deadline).await?.is_some() {
+                let received = buffer.len() - header_end;
+                return Ok(ReadResult::Reject(HTTPResponse::new(400, &format!(
+                    "Bad Request: body has {} of the {} bytes declared by Content-Length", received, content_length
+                ))));
+            }
+        }
+
+        Ok(ReadResult::Request(buffer.drain(..request_end).collect()))
+    }
 
-            // handle global middleware chain
-            let mut final_request: Result<HTTPRequest, HTTPResponse> = Ok(request.clone());
-            for middleware in middleware.iter() {
-                final_request = match final_request {
-                    Ok(req) => (middleware)(req),
-                    Err(res) => {
-                        Err(res)
+    // The request handed on is the header block plus the de-chunked body
+    async fn read_chunked_body<S: AsyncRead + Unpin>(&self, stream: &mut S, buffer: &mut Vec<u8>, header_end: usize, deadline: Instant) -> std::io::Result<ReadResult> {
+        loop {
+            match decode_chunked(buffer, header_end, self.max_body_size) {
+                Ok(ChunkedBody::Complete { body, end }) => {
+                    let mut raw_request: Vec<u8> = buffer.drain(..end).take(header_end).collect();
+                    raw_request.extend_from_slice(&body);
+                    return Ok(ReadResult::Request(raw_request));
+                }
+                Ok(ChunkedBody::Incomplete) => {
+                    if let Some(stop) = Self::read_more(stream, buffer, deadline).await? {
+                        return Ok(stop);
                     }
                 }
+                Ok(ChunkedBody::TooLarge) => {
+                    return Ok(ReadResult::Reject(HTTPResponse::new(413, "Payload Too Large")));
+                }
+                Err(e) => {
+                    return Ok(ReadResult::Reject(HTTPResponse::new(400, &format!("Bad Request: {}", e))));
+                }
             }
-            let request_to_route = match final_request {
-                Ok(req) => req,
-                Err(res) => {
-                    stream.write_all(res.to_http_string().as_bytes()).await?;
-                    continue;
+        }
+    }
+
+    // Returns Some when reading should stop (disconnect or deadline passed)
+    async fn read_more<S: AsyncRead + Unpin>(stream: &mut S, buffer: &mut Vec<u8>, deadline: Instant) -> std::io::Result<Option<ReadResult>> {
+        match timeout_at(deadline, Self::fill_buffer(stream, buffer)).await {
+            Ok(Ok(true)) => Ok(None),
+            Ok(Ok(false)) => Ok(Some(ReadResult::Closed)),
+            Ok(Err(e)) => Err(e),
+            // An idle keep-alive connection just closes; a half-sent request gets 408
+            Err(_) if buffer.is_empty() => Ok(Some(ReadResult::Closed)),
+            Err(_) => Ok(Some(ReadResult::Reject(HTTPResponse::new(408, "Request Timeout")))),
+        }
+    }
+
+    // Returns false once the client has disconnected
+    async fn fill_buffer<S: AsyncRead + Unpin>(stream: &mut S, buffer: &mut Vec<u8>) -> std::io::Result<bool> {
+        let mut chunk = [0; 4096];
+        let n_bytes = stream.read(&mut chunk).await?;
+        buffer.extend_from_slice(&chunk[..n_bytes]);
+        Ok(n_bytes > 0)
+    }
+
+    // Serve HTTP over an already-connected stream, e.g. a tokio::io::duplex
+    // pipe in tests. Returns when the client closes or asks to.
+    pub async fn serve_connection<S>(&self, stream: S) -> Result<(), Box<dyn std::error::Error>>
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let (_stop_tx, stop_rx) = watch::channel(false);
+        self.handle_connection(stream, None, stop_rx).await
+    }
+
+    // Works over any byte stream, plain TCP or TLS
+    async fn handle_connection<S>(&self, mut stream: S, peer: Option<SocketAddr>, mut shutdown: watch::Receiver<bool>) -> Result<(), Box<dyn std::error::Error>>
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let mut buffer = Vec::new();
+        let mut upgrade: Option<OnUpgrade> = None;
+        let mut served = 0;
+
+        loop {
+            // Waiting for the next request is abandoned on shutdown
+            let read_result = tokio::select! {
+                result = self.read_request(&mut stream, &mut buffer) => result?,
+                _ = shutdown.wait_for(|stopping| *stopping) => break,
+            };
+
+            let raw_request = match read_result {
+                ReadResult::Request(raw) => raw,
+                ReadResult::Reject(res) => {
+                    // The rest of the stream can't be trusted, so close after rejecting
+                    let mut res = self.error_page_for(res);
+                    res.set_header("Connection", "close");
+                    self.add_default_headers(&mut res);
+                    stream.write_all(&res.to_bytes()).await?;
+                    break;
                 }
+                ReadResult::Closed => break, // Client disconnected
             };
 
-            // Try routers until one handles it
-            let mut response = None;
-            for router in routers.iter() {
-                let res = router.handle_request(request_to_route.clone());
-                if res.status.code() != 404 {
-                    response = Some(res);
+            // Parse request (a malformed request closes the connection)
+            let mut request = match HTTPRequest::from_bytes(&raw_request) {
+                Ok(req) => req,
+                Err(e) => {
+                    let mut res = self.error_page_for(HTTPResponse::new(400, &format!("Bad Request: {}", e)))
+                        .with_header("Connection", "close");
+                    self.add_default_headers(&mut res);
+                    stream.write_all(&res.to_bytes()).await?;
                     break;
                 }
-            }
+            };
 
-            // Send response
-            let final_response = response.unwrap_or_else(|| {
-                HTTPResponse::not_found("No router matched this path")
+            request.remote_addr = peer;
+            let started = Instant::now();
+            let mut log_entry = self.access_log.as_ref().map(|_| AccessLogEntry {
+                method: request.method.clone(),
+                path: match request.query_string.as_str() {
+                    "" => request.route.clone(),
+                    query => format!("{}?{}", request.route, query),
+                },
+                version: request.version.clone(),
+                status: 0,
+                body_size: 0,
+                elapsed: Duration::ZERO,
+                remote_addr: peer,
             });
 
-            stream.write_all(final_response.to_http_string().as_bytes()).await?;
+            // A request already being handled finishes, but the connection closes after it
+            let mut keep_alive = request.keep_alive() && !*shutdown.borrow();
+            served += 1;
+            if self.max_requests_per_connection.is_some_and(|limit| served >= limit) {
+                keep_alive = false;
+            }
+            let is_head = request.method == Method::Head;
+            let is_http_1_0 = request.is_http_1_0();
+            // HEAD advertises the identity body's length, so it's never gzipped
+            let accept_encoding = request.get_header("Accept-Encoding")
+                .filter(|_| !is_head);
+            let if_none_match = request.get_header("If-None-Match")
+                .filter(|_| request.method == Method::Get || is_head);
+            let if_modified_since = request.get_header("If-Modified-Since")
+                .filter(|_| request.method == Method::Get || is_head);
+
+            // A panicking handler or middleware costs this request, not the connection
+            let mut final_response = CatchPanic(Box::pin(self.dispatch(request))).await
+                .unwrap_or_else(|| self.error_page_for(HTTPResponse::internal_error("Internal Server Error")));
+            // If-None-Match takes precedence when both are sent
+            if let Some(if_none_match) = &if_none_match {
+                final_response = final_response.not_modified_for(if_none_match);
+            } else if let Some(if_modified_since) = &if_modified_since {
+                final_response = final_response.not_modified_since(if_modified_since);
+            }
+            if let (Some(options), Some(accept_encoding)) = (&self.compression, &accept_encoding) {
+                final_response = final_response.compress_with(accept_encoding, options);
+            }
+            if is_head {
+                final_response = final_response.without_body();
+            }
+            // No chunked encoding for HTTP/1.0: send the stream as-is and let
+            // closing the connection mark the end of the body
+            let framed = !is_http_1_0;
+            if !framed && final_response.chunks().is_some() {
+                final_response.remove_header("Transfer-Encoding");
+                keep_alive = false;
+            }
+            // Shutdown may have started while the handler ran
+            keep_alive &= !*shutdown.borrow();
+            // A 101 keeps its own "Connection: Upgrade"
+            upgrade = final_response.upgrade().cloned();
+            if upgrade.is_none() {
+                final_response.set_header("Connection", if keep_alive { "keep-alive" } else { "close" });
+            }
+
+            self.add_default_headers(&mut final_response);
+            stream.write_all(&final_response.to_bytes()).await?;
+            let mut body_size = final_response.body().len();
+            if let Some(chunks) = final_response.chunks() {
+                body_size = write_chunks(chunks, &mut stream, framed).await?;
+            } else if let Some(file) = final_response.file_body() {
+                body_size = write_file(file, &mut stream).await?;
+            }
+
+            if let (Some(logger), Some(entry)) = (&self.access_log, log_entry.as_mut()) {
+                entry.status = final_response.status.code();
+                entry.body_size = body_size;
+                entry.elapsed = started.elapsed();
+                logger(entry);
+            }
+
+            if !keep_alive || upgrade.is_some() {
+                break;
+            }
+        }
+
+        // The connection now belongs to the WebSocket handler, which is
+        // dropped (closing the socket) if the server shuts down first
+        if let Some(run) = upgrade.and_then(|upgrade| upgrade.take()) {
+            let socket = WebSocket::new(Box::new(stream), buffer);
+            tokio::select! {
+                _ = run(socket) => {}
+                _ = shutdown.wait_for(|stopping| *stopping) => {}
+            }
         }
 
         Ok(())
     }
 
     pub async fn run(self) -> Result<(), Box<dyn std::error::Error>> {
+        self.run_until(std::future::pending()).await
+    }
+
+    // For apps without their own Tokio setup: builds a multi-threaded
+    // runtime (see workers) and runs the server on it, blocking this thread.
+    // Don't call from inside a runtime - Tokio panics on nested runtimes.
+    pub fn run_blocking(self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut runtime = tokio::runtime::Builder::new_multi_thread();
+        if let Some(threads) = self.worker_threads {
+            runtime.worker_threads(threads);
+        }
+        runtime.enable_all().build()?.block_on(self.run())
+    }
+
+    // Serve until `shutdown` completes, then stop accepting, let in-flight
+    // requests finish and return
+    pub async fn run_until(self, shutdown: impl Future<Output = ()>) -> Result<(), Box<dyn std::error::Error>> {
+        self.serve(None, shutdown).await
+    }
+
+    // Bind `addr` now and serve later:
+    //   let bound = server.bind().await?;
+    //   println!("listening on {}", bound.local_addr());
+    //   bound.run().await
+    pub async fn bind(self) -> Result<BoundServer, Box<dyn std::error::Error>> {
         let listener = TcpListener::bind(&self.addr).await?;
-        println!("Started HTTP Server at {}", self.addr);
+        let local_addr = listener.local_addr()?;
+        Ok(BoundServer { server: self, listener, local_addr })
+    }
+
+    // Serve on a listener bound by the caller, e.g. "127.0.0.1:0" in tests
+    // where listener.local_addr() gives the assigned port. `addr` is unused.
+    pub async fn run_on(self, listener: TcpListener) -> Result<(), Box<dyn std::error::Error>> {
+        self.run_on_until(listener, std::future::pending()).await
+    }
+
+    pub async fn run_on_until(self, listener: TcpListener, shutdown: impl Future<Output = ()>) -> Result<(), Box<dyn std::error::Error>> {
+        self.serve_listener(listener, None, shutdown).await
+    }
+
+    // Serve HTTPS using a PEM certificate chain and private key
+    pub async fn run_tls(self, cert_path: &str, key_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let acceptor = tls::load_acceptor(cert_path, key_path)?;
+        self.serve(Some(acceptor), std::future::pending()).await
+    }
+
+    // Serve over a Unix domain socket at `path` (the TCP addr is unused).
+    // A stale socket file from a previous run is replaced.
+    #[cfg(unix)]
+    pub async fn run_unix(self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.run_unix_until(path, std::future::pending()).await
+    }
+
+    // Like run_until, but on a Unix socket. The socket file is removed on the way out.
+    #[cfg(unix)]
+    pub async fn run_unix_until(self, path: &str, shutdown: impl Future<Output = ()>) -> Result<(), Box<dyn std::error::Error>> {
+        let _ = std::fs::remove_file(path);
+        let listener = UnixListener::bind(path)?;
+        let _socket_file = SocketFile(path);
+        println!("Started HTTP Server at unix:{}", path);
+
+        // Unix peers have no SocketAddr, so remote_addr stays None
+        self.accept_loop(listener, shutdown, |server, stream, _, stop_rx| async move {
+            if let Err(e) = server.handle_connection(stream, None, stop_rx).await {
+                eprintln!("Connection error on unix socket: {}", e);
+            }
+        }).await?;
 
-        let routers = Arc::new(self.routers);
-        let middleware = Arc::new(self.middleware);
+        println!("Stopped HTTP Server at unix:{}", path);
+        Ok(())
+    }
+
+    // The accept loop behind every listener: wait for a free connection slot,
+    // accept, and run `connection` for it in its own task. Once `shutdown`
+    // completes, stop accepting and drain what's still open.
+    async fn accept_loop<L, C, F>(self, listener: L, shutdown: impl Future<Output = ()>, mut connection: C) -> std::io::Result<()>
+    where
+        L: Listener,
+        C: FnMut(Arc<HTTPServer>, L::Stream, L::Addr, watch::Receiver<bool>) -> F,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let server = Arc::new(self);
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let mut connections = JoinSet::new();
+        tokio::pin!(shutdown);
 
         loop {
-            let (stream, addr) = listener.accept().await?;
-            let routers = Arc::clone(&routers);
-            let middleware = Arc::clone(&middleware);
+            // Wait for a free slot before accepting, so excess clients queue in the backlog
+            let permit = tokio::select! {
+                permit = server.connection_permit() => permit,
+                _ = &mut shutdown => break,
+            };
+            let (stream, addr) = tokio::select! {
+                accepted = listener.accept() => accepted?,
+                _ = &mut shutdown => break,
+            };
+            let handled = connection(Arc::clone(&server), stream, addr, stop_rx.clone());
+
+            connections.spawn(async move {
+                let _permit = permit;  // ← Released when the connection closes
+                handled.await;
+            });
+
+            // Reap finished connections so the set doesn't grow forever
+            while connections.try_join_next().is_some() {}
+        }
+
+        drop(listener);
+        let _ = stop_tx.send(true);
+        server.drain(connections).await;
+        Ok(())
+    }
 
-            tokio::spawn(async move {
-                if let Err(e) = Self::handle_connection(stream, routers, middleware).await {
+    // Wait for open connections to finish, or force-close whatever is left
+    // once shutdown_timeout runs out
+    async fn drain(&self, mut connections: JoinSet<()>) {
+        let all_finished = async {
+            while connections.join_next().await.is_some() {}
+        };
+        let Some(grace) = self.shutdown_timeout else {
+            return all_finished.await;
+        };
+
+        if timeout(grace, all_finished).await.is_err() {
+            eprintln!("Shutdown timeout: closing {} connection(s) still in flight", connections.len());
+            connections.shutdown().await;
+        }
+    }
+
+    // None when connections are unlimited
+    async fn connection_permit(&self) -> Option<OwnedSemaphorePermit> {
+        let limit = Arc::clone(self.connection_limit.as_ref()?);
+        limit.acquire_owned().await.ok()
+    }
+
+    async fn serve(self, tls: Option<TlsAcceptor>, shutdown: impl Future<Output = ()>) -> Result<(), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind(&self.addr).await?;
+        self.serve_listener(listener, tls, shutdown).await
+    }
+
+    async fn serve_listener(self, listener: TcpListener, tls: Option<TlsAcceptor>, shutdown: impl Future<Output = ()>) -> Result<(), Box<dyn std::error::Error>> {
+        let local_addr = listener.local_addr()?;
+        let scheme = if tls.is_some() { "HTTPS" } else { "HTTP" };
+        println!("Started {} Server at {}", scheme, local_addr);
+
+        self.accept_loop(listener, shutdown, |server, stream, addr, stop_rx| {
+            if let Err(e) = server.apply_socket_options(&stream) {
+                eprintln!("Couldn't set socket options for {}: {}", addr, e);
+            }
+            let tls = tls.clone();
+
+            async move {
+                let result = match tls {
+                    // Handshake inside the task so a slow client can't stall accept()
+                    Some(acceptor) => match timeout(server.read_timeout, acceptor.accept(stream)).await {
+                        Ok(Ok(tls_stream)) => server.handle_connection(tls_stream, Some(addr), stop_rx).await,
+                        Ok(Err(e)) => Err(format!("TLS handshake failed: {}", e).into()),
+                        Err(_) => Err("TLS handshake timed out".into()),
+                    },
+                    None => server.handle_connection(stream, Some(addr), stop_rx).await,
+                };
+                if let Err(e) = result {
                     eprintln!("Connection error from {}: {}", addr, e);
                 }
-            });
+            }
+        }).await?;
+
+        println!("Stopped {} Server at {}", scheme, local_addr);
+        Ok(())
+    }
+}
+
+impl BoundServer {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub async fn run(self) -> Result<(), Box<dyn std::error::Error>> {
+        self.server.run_on(self.listener).await
+    }
+
+    pub async fn run_until(self, shutdown: impl Future<Output = ()>) -> Result<(), Box<dyn std::error::Error>> {
+        self.server.run_on_until(self.listener, shutdown).await
+    }
+}
+
+// What accept_loop needs from a listener, so TCP and Unix sockets share it
+trait Listener {
+    type Stream;
+    type Addr;
+
+    fn accept(&self) -> impl Future<Output = std::io::Result<(Self::Stream, Self::Addr)>>;
+}
+
+impl Listener for TcpListener {
+    type Stream = TcpStream;
+    type Addr = SocketAddr;
+
+    fn accept(&self) -> impl Future<Output = std::io::Result<(TcpStream, SocketAddr)>> {
+        TcpListener::accept(self)
+    }
+}
+
+#[cfg(unix)]
+impl Listener for UnixListener {
+    type Stream = UnixStream;
+    type Addr = tokio::net::unix::SocketAddr;
+
+    fn accept(&self) -> impl Future<Output = std::io::Result<(UnixStream, Self::Addr)>> {
+        UnixListener::accept(self)
+    }
+}
+
+// Deletes the socket file when the Unix listener goes away, even on error
+#[cfg(unix)]
+struct SocketFile<'a>(&'a str);
+
+#[cfg(unix)]
+impl Drop for SocketFile<'_> {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(self.0);
+    }
+}
+
+// Resolves to None if the wrapped future panics while being polled
+struct CatchPanic<F>(Pin<Box<F>>);
+
+impl<F: Future> Future for CatchPanic<F> {
+    type Output = Option<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match std::panic::catch_unwind(AssertUnwindSafe(|| self.0.as_mut().poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Some(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(_) => Poll::Ready(None),
+        }
+    }
+}
+
+fn header_too_large() -> HTTPResponse {
+    HTTPResponse::new(431, "Request Header Fields Too Large")
+}
+
+// Index just past the blank line that ends the header block
+fn find_header_end(buffer: &[u8]) -> Option<usize> {
+    buffer.windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|pos| pos + 4)
+}
+
+// Repeated or comma-listed values are fine as long as they agree; differing
+// ones would let the body be framed one way here and another way upstream
+fn parse_content_length(header_block: &[u8]) -> Result<usize, String> {
+    let mut length = None;
+    for value in find_headers(header_block, "Content-Length").iter().flat_map(|value| value.split(',')) {
+        let value = value.trim();
+        let parsed: usize = value.parse().map_err(|_| format!("Invalid Content-Length: {}", value))?;
+        if length.is_some_and(|length| length != parsed) {
+            return Err("conflicting Content-Length values".to_string());
         }
+        length = Some(parsed);
+    }
+    Ok(length.unwrap_or(0))
+}
+
+// The last transfer coding has to be chunked, or there's no telling where
+// the body ends and its bytes would be read as the next request
+fn is_chunked(header_block: &[u8]) -> Result<bool, HTTPResponse> {
+    let codings: Vec<String> = find_headers(header_block, "Transfer-Encoding").iter()
+        .flat_map(|value| value.split(','))
+        .map(|coding| coding.trim().to_ascii_lowercase())
+        .filter(|coding| !coding.is_empty())
+        .collect();
+
+    match codings.last().map(String::as_str) {
+        None => Ok(false),
+        Some("chunked") => Ok(true),
+        Some("gzip" | "x-gzip" | "deflate" | "compress" | "x-compress" | "identity") =>
+            Err(HTTPResponse::new(400, "Bad Request: Transfer-Encoding must end with chunked")),
+        Some(coding) => Err(HTTPResponse::new(501, &format!("Not Implemented: unsupported Transfer-Encoding {}", coding))),
     }
-}
\ No newline at end of file
+}
+
+// "Expect: 100-continue", only meaningful from HTTP/1.1 clients
+fn expects_continue(header_block: &[u8]) -> bool {
+    let is_http_1_0 = header_block.split(|&byte| byte == b'\r')
+        .next()
+        .is_some_and(|request_line| request_line.ends_with(b"HTTP/1.0"));
+    !is_http_1_0 && find_header(header_block, "Expect")
+        .is_some_and(|value| value.eq_ignore_ascii_case("100-continue"))
+}
+
+// First value of a header in the raw header block, trimmed
+fn find_header(header_block: &[u8], name: &str) -> Option<String> {
+    find_headers(header_block, name).into_iter().next()
+}
+
+// Every value of a header in the raw header block, trimmed
+fn find_headers(header_block: &[u8], name: &str) -> Vec<String> {
+    let headers = String::from_utf8_lossy(header_block);
+
+    headers.split("\r\n")
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(header, _)| header.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim().to_string())
+        .collect()
+}
@@ -0,0 +1,25 @@
+use std::sync::Arc;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+
+// Build a TLS acceptor from a PEM certificate chain and private key
+pub(crate) fn load_acceptor(cert_path: &str, key_path: &str) -> Result<TlsAcceptor, String> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Failed to read certificate {}: {}", cert_path, e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {}", cert_path));
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| format!("Failed to read private key {}: {}", key_path, e))?;
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Invalid certificate/key pair: {}", e))?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
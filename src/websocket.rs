@@ -0,0 +1,300 @@
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use ring::digest;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use crate::requests::{HTTPRequest, Method};
+use crate::responses::HTTPResponse;
+
+// RFC 6455: appended to the client's key before hashing
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// Bigger messages (after joining fragments) close the socket with 1009
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+pub type WebSocketFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+pub type WebSocketHandler = Arc<dyn Fn(HTTPRequest, WebSocket) -> WebSocketFuture + Send + Sync>;
+
+// Whatever the connection was accepted on: TCP, TLS or a Unix socket
+pub trait UpgradedStream: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<S: AsyncRead + AsyncWrite + Send + Unpin> UpgradedStream for S {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>)
+}
+
+// Server side of an upgraded connection. Pings are answered and fragmented
+// messages joined inside recv().
+pub struct WebSocket {
+    stream: Box<dyn UpgradedStream>,
+    buffer: Vec<u8>,  // ← Starts with anything the client sent right after the handshake
+    closed: bool
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>
+}
+
+impl WebSocket {
+    pub(crate) fn new(stream: Box<dyn UpgradedStream>, buffer: Vec<u8>) -> Self {
+        Self { stream, buffer, closed: false }
+    }
+
+    // Next text or binary message, or None once the client has closed.
+    // Protocol violations close the socket and come back as InvalidData.
+    pub async fn recv(&mut self) -> io::Result<Option<Message>> {
+        let mut partial: Option<(u8, Vec<u8>)> = None;
+
+        while !self.closed {
+            let Some(frame) = self.read_frame().await? else {
+                self.closed = true;
+                break;
+            };
+
+            match frame.opcode {
+                OP_PING => self.write_frame(OP_PONG, &frame.payload).await?,
+                OP_PONG => {}
+                OP_CLOSE => {
+                    // Echo the status code back and stop
+                    let code = frame.payload.get(..2).unwrap_or_default().to_vec();
+                    self.write_frame(OP_CLOSE, &code).await?;
+                    self.closed = true;
+                }
+                OP_TEXT | OP_BINARY if partial.is_none() => partial = Some((frame.opcode, frame.payload)),
+                OP_CONTINUATION if partial.is_some() => {
+                    if let Some((_, data)) = partial.as_mut() {
+                        data.extend_from_slice(&frame.payload);
+                    }
+                }
+                _ => return self.fail(1002, "Unexpected frame").await,
+            }
+
+            if partial.as_ref().is_some_and(|(_, data)| data.len() > MAX_MESSAGE_SIZE) {
+                return self.fail(1009, "Message too large").await;
+            }
+            if !frame.fin || frame.opcode >= OP_CLOSE {
+                continue;
+            }
+
+            let Some((opcode, data)) = partial.take() else {
+                continue;
+            };
+            if opcode == OP_BINARY {
+                return Ok(Some(Message::Binary(data)));
+            }
+            return match String::from_utf8(data) {
+                Ok(text) => Ok(Some(Message::Text(text))),
+                Err(_) => self.fail(1007, "Text message is not UTF-8").await,
+            };
+        }
+
+        Ok(None)
+    }
+
+    pub async fn send(&mut self, message: Message) -> io::Result<()> {
+        match message {
+            Message::Text(text) => self.write_frame(OP_TEXT, text.as_bytes()).await,
+            Message::Binary(data) => self.write_frame(OP_BINARY, &data).await,
+        }
+    }
+
+    pub async fn send_text(&mut self, text: &str) -> io::Result<()> {
+        self.write_frame(OP_TEXT, text.as_bytes()).await
+    }
+
+    // Send a normal (1000) close frame
+    pub async fn close(mut self) -> io::Result<()> {
+        if !self.closed {
+            self.write_frame(OP_CLOSE, &1000u16.to_be_bytes()).await?;
+        }
+        self.stream.shutdown().await
+    }
+
+    async fn fail(&mut self, code: u16, reason: &str) -> io::Result<Option<Message>> {
+        let mut payload = code.to_be_bytes().to_vec();
+        payload.extend_from_slice(reason.as_bytes());
+        self.write_frame(OP_CLOSE, &payload).await?;
+        self.closed = true;
+        Err(io::Error::new(io::ErrorKind::InvalidData, reason))
+    }
+
+    // None if the client disconnected
+    async fn read_frame(&mut self) -> io::Result<Option<Frame>> {
+        if !self.fill(2).await? {
+            return Ok(None);
+        }
+        let (first, second) = (self.buffer[0], self.buffer[1]);
+        let fin = first & 0x80 != 0;
+        let opcode = first & 0x0F;
+
+        // No extensions are negotiated, and clients must mask every frame
+        if first & 0x70 != 0 || second & 0x80 == 0 {
+            return self.fail(1002, "Invalid frame header").await.map(|_| None);
+        }
+
+        let (length, header_len) = match second & 0x7F {
+            126 => {
+                if !self.fill(4).await? {
+                    return Ok(None);
+                }
+                (u16::from_be_bytes([self.buffer[2], self.buffer[3]]) as usize, 4)
+            }
+            127 => {
+                if !self.fill(10).await? {
+                    return Ok(None);
+                }
+                let mut length = [0; 8];
+                length.copy_from_slice(&self.buffer[2..10]);
+                (usize::try_from(u64::from_be_bytes(length)).unwrap_or(usize::MAX), 10)
+            }
+            length => (length as usize, 2),
+        };
+
+        // Control frames are short and never fragmented
+        if opcode >= OP_CLOSE && (length > 125 || !fin) {
+            return self.fail(1002, "Invalid control frame").await.map(|_| None);
+        }
+        if length > MAX_MESSAGE_SIZE {
+            return self.fail(1009, "Message too large").await.map(|_| None);
+        }
+
+        let frame_len = header_len + 4 + length;
+        if !self.fill(frame_len).await? {
+            return Ok(None);
+        }
+
+        let frame: Vec<u8> = self.buffer.drain(..frame_len).collect();
+        let mask = &frame[header_len..header_len + 4];
+        let payload = frame[header_len + 4..].iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ mask[i % 4])
+            .collect();
+
+        Ok(Some(Frame { fin, opcode, payload }))
+    }
+
+    // Read until at least `len` bytes are buffered; false on disconnect
+    async fn fill(&mut self, len: usize) -> io::Result<bool> {
+        while self.buffer.len() < len {
+            let mut chunk = [0; 4096];
+            let n_bytes = self.stream.read(&mut chunk).await?;
+            if n_bytes == 0 {
+                return Ok(false);
+            }
+            self.buffer.extend_from_slice(&chunk[..n_bytes]);
+        }
+        Ok(true)
+    }
+
+    // Server frames are never masked or fragmented
+    async fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mut frame = vec![0x80 | opcode];
+        match payload.len() {
+            len @ 0..=125 => frame.push(len as u8),
+            len if len <= u16::MAX as usize => {
+                frame.push(126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(payload);
+
+        self.stream.write_all(&frame).await?;
+        self.stream.flush().await
+    }
+}
+
+impl fmt::Debug for WebSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSocket").field("closed", &self.closed).finish()
+    }
+}
+
+type UpgradeFn = Box<dyn FnOnce(WebSocket) -> WebSocketFuture + Send>;
+
+// Carried by a 101 response: what to run on the connection once the
+// handshake has been written. Shared so HTTPResponse stays Clone.
+#[derive(Clone)]
+pub struct OnUpgrade(Arc<Mutex<Option<UpgradeFn>>>);
+
+impl OnUpgrade {
+    fn new(upgrade: UpgradeFn) -> Self {
+        Self(Arc::new(Mutex::new(Some(upgrade))))
+    }
+
+    pub(crate) fn take(&self) -> Option<UpgradeFn> {
+        self.0.lock().ok()?.take()
+    }
+}
+
+impl fmt::Debug for OnUpgrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnUpgrade")
+    }
+}
+
+// The 101 handshake for `req`, running `handler` on the connection once it's
+// sent. Requests that aren't a valid upgrade get 426 or 400 instead.
+pub fn upgrade(req: HTTPRequest, handler: WebSocketHandler) -> HTTPResponse {
+    let key = match check_handshake(&req) {
+        Ok(key) => key,
+        Err(res) => return res,
+    };
+
+    let mut res = HTTPResponse::new(101, "");
+    res.headers.clear();
+    res.with_header("Upgrade", "websocket")
+        .with_header("Connection", "Upgrade")
+        .with_header("Sec-WebSocket-Accept", &accept_key(&key))
+        .with_upgrade(OnUpgrade::new(Box::new(move |socket| handler(req, socket))))
+}
+
+// Sec-WebSocket-Accept for a client's Sec-WebSocket-Key
+pub fn accept_key(key: &str) -> String {
+    // RFC 6455 fixes the hash; SHA-1 is fine here, it's not guarding a secret
+    let digest = digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, format!("{}{}", key.trim(), ACCEPT_GUID).as_bytes());
+    BASE64.encode(digest.as_ref())
+}
+
+// The client's key, if this is a well-formed version 13 upgrade
+fn check_handshake(req: &HTTPRequest) -> Result<String, HTTPResponse> {
+    let upgrade = req.get_header("Upgrade").is_some_and(|value| has_token(&value, "websocket"));
+    let connection = req.get_header("Connection").is_some_and(|value| has_token(&value, "upgrade"));
+    if req.method != Method::Get || !upgrade || !connection {
+        return Err(HTTPResponse::new(426, "Upgrade Required: expected a WebSocket handshake")
+            .with_header("Upgrade", "websocket"));
+    }
+
+    if req.get_header("Sec-WebSocket-Version").as_deref().map(str::trim) != Some("13") {
+        return Err(HTTPResponse::new(426, "Upgrade Required: unsupported WebSocket version")
+            .with_header("Sec-WebSocket-Version", "13"));
+    }
+
+    // Must be 16 random bytes, base64-encoded
+    match req.get_header("Sec-WebSocket-Key") {
+        Some(key) if BASE64.decode(key.trim()).is_ok_and(|nonce| nonce.len() == 16) => Ok(key),
+        _ => Err(HTTPResponse::bad_request("Bad Request: invalid Sec-WebSocket-Key")),
+    }
+}
+
+// Comma-separated header tokens compare case-insensitively ("keep-alive, Upgrade")
+fn has_token(value: &str, token: &str) -> bool {
+    value.split(',').any(|part| part.trim().eq_ignore_ascii_case(token))
+}
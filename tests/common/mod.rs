@@ -0,0 +1,135 @@
+// Shared by the integration tests: run a server on an ephemeral port and
+// talk to it with a bare-bones HTTP/1.1 client.
+#![allow(dead_code)]  // ← Each test binary only uses some of these
+
+use std::net::SocketAddr;
+use http_server_from_scratch::server::HTTPServer;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+
+// The server stops (gracefully) when this is dropped
+pub struct TestServer {
+    pub addr: SocketAddr,
+    pub base_url: String,  // ← e.g. "http://127.0.0.1:49152"
+    _stop: oneshot::Sender<()>
+}
+
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>
+}
+
+impl TestServer {
+    // `server` should be created with HTTPServer::new("127.0.0.1:0")
+    pub async fn start(server: HTTPServer) -> Self {
+        let bound = server.bind().await.expect("bind the test server");
+        let addr = bound.local_addr();
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+
+        tokio::spawn(async move {
+            let shutdown = async {
+                let _ = stop_rx.await;
+            };
+            if let Err(e) = bound.run_until(shutdown).await {
+                panic!("test server failed: {}", e);
+            }
+        });
+
+        Self {
+            addr,
+            base_url: format!("http://{}", addr),
+            _stop: stop_tx
+        }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    // For tests that write raw bytes or reuse one connection
+    pub async fn connect(&self) -> TcpStream {
+        TcpStream::connect(self.addr).await.expect("connect to test server")
+    }
+
+    pub async fn get(&self, path: &str) -> TestResponse {
+        self.request("GET", path, &[], b"").await
+    }
+
+    // One request per connection (Connection: close), read until the server hangs up
+    pub async fn request(&self, method: &str, path: &str, headers: &[(&str, &str)], body: &[u8]) -> TestResponse {
+        let mut stream = TcpStream::connect(self.addr).await.expect("connect to test server");
+
+        let mut request = format!("{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n", method, path, self.addr);
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if !body.is_empty() {
+            request.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        request.push_str("\r\n");
+
+        let mut raw = request.into_bytes();
+        raw.extend_from_slice(body);
+        stream.write_all(&raw).await.expect("send request");
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.expect("read response");
+        TestResponse::parse(&response)
+    }
+}
+
+impl TestResponse {
+    // Read exactly one Content-Length framed response, leaving the connection
+    // open. The head is read a byte at a time so responses queued behind
+    // this one (pipelining) stay on the stream for the next call.
+    pub async fn read_from(stream: &mut TcpStream) -> Self {
+        let mut raw = Vec::new();
+        while !raw.ends_with(b"\r\n\r\n") {
+            let byte = stream.read_u8().await.expect("connection closed before a full response arrived");
+            raw.push(byte);
+        }
+
+        let head = Self::parse(&raw);
+        let length: usize = head.header("Content-Length").and_then(|len| len.parse().ok()).unwrap_or(0);
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await.expect("connection closed before a full response arrived");
+        raw.extend_from_slice(&body);
+        Self::parse(&raw)
+    }
+
+    pub fn parse(raw: &[u8]) -> Self {
+        let header_end = raw.windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .expect("response has a complete header block");
+        let head = String::from_utf8_lossy(&raw[..header_end]);
+        let mut lines = head.split("\r\n");
+
+        let status = lines.next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|code| code.parse().ok())
+            .expect("response has a status line");
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+
+        Self {
+            status,
+            headers,
+            body: raw[header_end + 4..].to_vec()
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::{TestResponse, TestServer};
+use http_server_from_scratch::requests::HTTPRequest;
+use http_server_from_scratch::responses::HTTPResponse;
+use http_server_from_scratch::routing::Router;
+use http_server_from_scratch::server::HTTPServer;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+async fn start() -> TestServer {
+    let router = Router::new("/")
+        .get("/about", |_req: HTTPRequest| HTTPResponse::ok("About"), vec![])
+        .get("/stream", |_req: HTTPRequest| {
+            let (sender, receiver) = mpsc::channel(4);
+            tokio::spawn(async move {
+                for part in ["one ", "two ", "three"] {
+                    let _ = sender.send(part.as_bytes().to_vec()).await;
+                }
+            });
+            HTTPResponse::chunked(receiver)
+        }, vec![]);
+    TestServer::start(HTTPServer::new("127.0.0.1:0").add_router(router)).await
+}
+
+#[tokio::test]
+async fn http_1_0_closes_after_the_response_by_default() {
+    let server = start().await;
+    let mut stream = server.connect().await;
+
+    stream.write_all(b"GET /about HTTP/1.0\r\n\r\n").await.unwrap();
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw).await.unwrap();  // ← Only returns because the server hung up
+    let res = TestResponse::parse(&raw);
+
+    assert_eq!(res.status, 200);
+    assert_eq!(res.header("Connection"), Some("close"));
+    assert_eq!(res.text(), "About");
+}
+
+#[tokio::test]
+async fn http_1_0_keep_alive_is_opt_in() {
+    let server = start().await;
+    let mut stream = server.connect().await;
+
+    stream.write_all(b"GET /about HTTP/1.0\r\nConnection: keep-alive\r\n\r\n").await.unwrap();
+    let first = TestResponse::read_from(&mut stream).await;
+    stream.write_all(b"GET /about HTTP/1.0\r\n\r\n").await.unwrap();
+    let second = TestResponse::read_from(&mut stream).await;
+
+    assert_eq!(first.header("Connection"), Some("keep-alive"));
+    assert_eq!(second.header("Connection"), Some("close"));
+}
+
+#[tokio::test]
+async fn http_1_1_keeps_the_connection_open_by_default() {
+    let server = start().await;
+    let mut stream = server.connect().await;
+
+    stream.write_all(b"GET /about HTTP/1.1\r\nHost: test\r\n\r\n").await.unwrap();
+    let first = TestResponse::read_from(&mut stream).await;
+    stream.write_all(b"GET /about HTTP/1.1\r\nHost: test\r\n\r\n").await.unwrap();
+    let second = TestResponse::read_from(&mut stream).await;
+
+    assert_eq!(first.header("Connection"), Some("keep-alive"));
+    assert_eq!(second.status, 200);
+    assert_eq!(second.text(), "About");
+}
+
+#[tokio::test]
+async fn streamed_body_is_chunked_for_1_1_and_close_delimited_for_1_0() {
+    let server = start().await;
+
+    let mut modern = server.connect().await;
+    modern.write_all(b"GET /stream HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n").await.unwrap();
+    let mut raw = Vec::new();
+    modern.read_to_end(&mut raw).await.unwrap();
+    let chunked = TestResponse::parse(&raw);
+
+    let mut legacy = server.connect().await;
+    legacy.write_all(b"GET /stream HTTP/1.0\r\nConnection: keep-alive\r\n\r\n").await.unwrap();
+    let mut raw = Vec::new();
+    legacy.read_to_end(&mut raw).await.unwrap();
+    let plain = TestResponse::parse(&raw);
+
+    assert_eq!(chunked.header("Transfer-Encoding"), Some("chunked"));
+    assert_eq!(chunked.text(), "4\r\none \r\n4\r\ntwo \r\n5\r\nthree\r\n0\r\n\r\n");
+    assert_eq!(plain.header("Transfer-Encoding"), None);
+    assert_eq!(plain.header("Content-Length"), None);
+    assert_eq!(plain.header("Connection"), Some("close"));
+    assert_eq!(plain.text(), "one two three");
+}
@@ -0,0 +1,223 @@
+use http_server_from_scratch::requests::{normalize_path, HTTPRequest, Method};
+use http_server_from_scratch::responses::HTTPResponse;
+use http_server_from_scratch::security::constant_time_eq;
+
+fn request(query: &str) -> HTTPRequest {
+    HTTPRequest::new(&format!("GET /items?{} HTTP/1.1\r\nHost: localhost\r\n\r\n", query)).unwrap()
+}
+
+#[test]
+fn query_u64_parses_large_values_and_falls_back() {
+    let req = request("offset=18446744073709551615&negative=-5&empty=&word=ten");
+
+    assert_eq!(req.query_u64("offset", 0), u64::MAX);
+    assert_eq!(req.query_u64("negative", 7), 7);
+    assert_eq!(req.query_u64("empty", 7), 7);
+    assert_eq!(req.query_u64("word", 7), 7);
+    assert_eq!(req.query_u64("missing", 7), 7);
+}
+
+#[test]
+fn query_f64_parses_decimals_and_falls_back() {
+    let req = request("price=19.99&exp=1e3&nan=NaN&inf=inf&empty=&word=cheap");
+
+    assert_eq!(req.query_f64("price", 0.0), 19.99);
+    assert_eq!(req.query_f64("exp", 0.0), 1000.0);
+    assert_eq!(req.query_f64("nan", 1.5), 1.5);
+    assert_eq!(req.query_f64("inf", 1.5), 1.5);
+    assert_eq!(req.query_f64("empty", 1.5), 1.5);
+    assert_eq!(req.query_f64("word", 1.5), 1.5);
+}
+
+#[test]
+fn query_parse_works_for_any_from_str_type() {
+    let req = request("port=8080&big=70000&ch=x&empty=");
+
+    assert_eq!(req.query_parse::<u16>("port", 80), 8080);
+    assert_eq!(req.query_parse::<u16>("big", 80), 80);
+    assert_eq!(req.query_parse::<char>("ch", '?'), 'x');
+    assert_eq!(req.query_parse::<i64>("empty", -1), -1);
+    assert_eq!(req.query_parse::<String>("empty", "none".to_string()), "none");
+    assert_eq!(req.query_parse::<String>("missing", "none".to_string()), "none");
+}
+
+#[test]
+fn existing_int_and_bool_accessors_are_unchanged() {
+    let req = request("page=3&verbose=true&bad=maybe");
+
+    assert_eq!(req.query_int("page", 1), 3);
+    assert!(req.query_bool("verbose", false));
+    assert!(!req.query_bool("bad", false));
+}
+
+#[test]
+fn request_paths_are_normalized() {
+    let req = HTTPRequest::new("GET /api//v1/./users/x/../42?sort=name HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+    assert_eq!(req.route, "/api/v1/users/42");
+    assert_eq!(req.query("sort", ""), "name");
+}
+
+#[test]
+fn normalize_path_stays_at_root_and_keeps_trailing_slashes() {
+    assert_eq!(normalize_path("/../../etc/passwd"), "/etc/passwd");
+    assert_eq!(normalize_path("/a/b/.."), "/a/");
+    assert_eq!(normalize_path("/a//"), "/a/");
+    assert_eq!(normalize_path("/.."), "/");
+    assert_eq!(normalize_path("//"), "/");
+    assert_eq!(normalize_path("/about/"), "/about/");
+    assert_eq!(normalize_path("*"), "*");
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+struct Profile {
+    name: String,
+    email: String,
+    nickname: Option<String>,
+    settings: Settings,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+struct Settings {
+    theme: String,
+    emails: bool,
+}
+
+fn patch(body: &str) -> HTTPRequest {
+    HTTPRequest::new(&format!("PATCH /me HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)).unwrap()
+}
+
+fn profile() -> Profile {
+    Profile {
+        name: "Ann".into(),
+        email: "ann@example.com".into(),
+        nickname: Some("annie".into()),
+        settings: Settings { theme: "light".into(), emails: true },
+    }
+}
+
+#[test]
+fn body_merge_into_only_touches_sent_fields() {
+    let mut current = profile();
+
+    patch(r#"{"name":"X","nickname":null,"settings":{"theme":"dark"}}"#).body_merge_into(&mut current).unwrap();
+
+    assert_eq!(current, Profile {
+        name: "X".into(),
+        email: "ann@example.com".into(),
+        nickname: None,
+        settings: Settings { theme: "dark".into(), emails: true },
+    });
+}
+
+#[test]
+fn body_merge_into_leaves_the_value_alone_on_bad_input() {
+    let mut current = profile();
+
+    let wrong_type = patch(r#"{"name":42}"#).body_merge_into(&mut current);
+    let not_object = patch(r#"["name"]"#).body_merge_into(&mut current);
+
+    assert!(wrong_type.is_err());
+    assert_eq!(not_object, Err("Request body must be a JSON object".to_string()));
+    assert_eq!(current, profile());
+}
+
+#[test]
+fn body_partial_returns_only_sent_fields() {
+    let fields = patch(r#"{"name":"X"}"#).body_partial().unwrap();
+
+    assert_eq!(fields.len(), 1);
+    assert_eq!(fields["name"], "X");
+}
+
+#[test]
+fn body_reports_an_empty_body_plainly() {
+    assert_eq!(patch("").body::<Settings>(), Err("Request body is empty".to_string()));
+    assert_eq!(patch(" \r\n\t ").body::<Settings>(), Err("Request body is empty".to_string()));
+    assert!(patch("{").body::<Settings>().unwrap_err().starts_with("Failed to deserialize request body"));
+}
+
+#[test]
+fn body_opt_treats_a_missing_body_as_none() {
+    assert_eq!(patch("").body_opt::<Settings>(), Ok(None));
+    assert_eq!(patch("  ").body_opt::<Settings>(), Ok(None));
+    assert_eq!(
+        patch(r#"{"theme":"dark","emails":false}"#).body_opt::<Settings>(),
+        Ok(Some(Settings { theme: "dark".into(), emails: false }))
+    );
+    assert!(patch("nope").body_opt::<Settings>().is_err());
+}
+
+// Same shape as the example app's api_key_check
+fn api_key_check(req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
+    match req.get_header("X-API-Key") {
+        Some(key) if constant_time_eq(&key, "mykey123") => Ok(req),
+        _ => Err(HTTPResponse::unauthorized("API key required")),
+    }
+}
+
+#[test]
+fn built_requests_can_be_run_through_middleware() {
+    let with_key = HTTPRequest::builder().path("/api/users").header("X-API-Key", "mykey123").build();
+    let without_key = HTTPRequest::builder().path("/api/users").build();
+    let wrong_key = HTTPRequest::builder().path("/api/users").header("x-api-key", "nope").build();
+
+    assert_eq!(api_key_check(with_key).map(|req| req.route).ok(), Some("/api/users".to_string()));
+    assert_eq!(api_key_check(without_key).unwrap_err().status.code(), 401);
+    assert_eq!(api_key_check(wrong_key).unwrap_err().status.code(), 401);
+}
+
+#[test]
+fn builder_fills_in_method_query_and_body() {
+    let req = HTTPRequest::builder()
+        .method("post")
+        .path("/api//items/./new?page=2")
+        .query("tag", "a b")
+        .query("tag", "c&d")
+        .param("id", "42")
+        .body_json(&Settings { theme: "dark".into(), emails: true })
+        .build();
+
+    assert_eq!(req.method, Method::Post);
+    assert_eq!(req.route, "/api/items/new");
+    assert_eq!(req.query("page", ""), "2");
+    assert_eq!(req.query_all("tag"), vec!["a b", "c&d"]);
+    assert_eq!(req.query_string, "page=2&tag=a+b&tag=c%26d");
+    assert_eq!(req.param("id", ""), "42");
+    assert_eq!(req.get_header("Content-Type").as_deref(), Some("application/json"));
+    assert_eq!(req.get_header("Content-Length").as_deref(), Some("30"));
+    assert_eq!(req.body::<Settings>(), Ok(Settings { theme: "dark".into(), emails: true }));
+}
+
+#[test]
+fn builder_defaults_to_get_root() {
+    let req = HTTPRequest::builder().build();
+
+    assert_eq!(req.method, Method::Get);
+    assert_eq!(req.route, "/");
+    assert_eq!(req.version, "HTTP/1.1");
+    assert!(req.headers.is_empty());
+    assert!(req.body_bytes().is_empty());
+}
+
+#[test]
+fn content_type_mime_splits_out_the_charset() {
+    let json = HTTPRequest::builder().header("Content-Type", "application/json; charset=utf-8").build();
+    let shouty = HTTPRequest::builder().header("Content-Type", "Text/HTML;Charset=\"UTF-8\"").build();
+    let form = HTTPRequest::builder().header("Content-Type", "multipart/form-data; boundary=xyz").build();
+    let none = HTTPRequest::builder().build();
+
+    assert_eq!(json.content_type().as_deref(), Some("application/json; charset=utf-8"));
+    assert_eq!(json.content_type_mime(), Some(("application/json".to_string(), Some("utf-8".to_string()))));
+    assert_eq!(shouty.content_type_mime(), Some(("text/html".to_string(), Some("utf-8".to_string()))));
+    assert_eq!(form.content_type_mime(), Some(("multipart/form-data".to_string(), None)));
+    assert_eq!(none.content_type(), None);
+    assert_eq!(none.content_type_mime(), None);
+}
+
+#[test]
+fn content_length_parses_the_declared_size() {
+    assert_eq!(patch(r#"{"name":"X"}"#).content_length(), Some(12));
+    assert_eq!(HTTPRequest::builder().header("Content-Length", "lots").build().content_length(), None);
+    assert_eq!(HTTPRequest::builder().build().content_length(), None);
+}
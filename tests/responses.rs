@@ -0,0 +1,137 @@
+mod common;
+
+use std::collections::BTreeMap;
+use common::TestServer;
+use http_server_from_scratch::requests::HTTPRequest;
+use http_server_from_scratch::responses::{HTTPResponse, IntoResponse, Json, SseEvent};
+use http_server_from_scratch::routing::Router;
+use http_server_from_scratch::server::HTTPServer;
+
+#[test]
+fn binary_bodies_survive_serialization() {
+    let res = HTTPResponse::from_bytes(200, vec![0xFF, 0x00, 0xFE, 0x0A]);
+
+    let wire = res.to_bytes();
+
+    assert_eq!(res.body(), &[0xFF, 0x00, 0xFE, 0x0A]);
+    assert!(wire.ends_with(b"\r\n\r\n\xFF\x00\xFE\x0A"));
+    assert_eq!(res.get_header("Content-Type"), Some("application/octet-stream"));
+}
+
+#[test]
+fn str_and_string_become_200_text() {
+    let from_str = "pong".into_response();
+    let from_string = String::from("pong").into_response();
+
+    for res in [from_str, from_string] {
+        assert_eq!(res.status.code(), 200);
+        assert_eq!(res.get_header("Content-Type"), Some("text/plain"));
+        assert_eq!(res.body(), b"pong");
+    }
+}
+
+#[test]
+fn tuple_sets_the_status() {
+    let res = (201, "done").into_response();
+    let accepted = (202, Json(vec![1, 2, 3])).into_response();
+
+    assert_eq!(res.status.code(), 201);
+    assert_eq!(res.body(), b"done");
+    assert_eq!(accepted.status.code(), 202);
+    assert_eq!(accepted.get_header("Content-Type"), Some("application/json"));
+    assert_eq!(accepted.body(), b"[1,2,3]");
+}
+
+#[test]
+fn json_serializes_any_value() {
+    let mut user = BTreeMap::new();
+    user.insert("name", "Ann");
+
+    let res = Json(user).into_response();
+
+    assert_eq!(res.status.code(), 200);
+    assert_eq!(res.get_header("Content-Type"), Some("application/json"));
+    assert_eq!(res.body(), br#"{"name":"Ann"}"#);
+}
+
+#[test]
+fn json_that_fails_to_serialize_is_500() {
+    // JSON object keys must be strings
+    let mut bad = BTreeMap::new();
+    bad.insert(vec![1], "value");
+
+    let res = Json(bad).into_response();
+
+    assert_eq!(res.status.code(), 500);
+}
+
+#[test]
+fn results_use_the_matching_side() {
+    let ok: Result<&str, HTTPResponse> = Ok("fine");
+    let err: Result<&str, String> = Err("missing field `name`".to_string());
+
+    assert_eq!(ok.into_response().status.code(), 200);
+    let err = err.into_response();
+    assert_eq!(err.status.code(), 400);
+    assert_eq!(err.body(), b"missing field `name`");
+}
+
+#[test]
+fn vary_accumulates_into_one_header() {
+    let res = HTTPResponse::ok("hi").vary("Accept").vary("Accept-Encoding").vary("accept");
+
+    let vary: Vec<&str> = res.headers.iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("Vary"))
+        .map(|(_, value)| value.as_str())
+        .collect();
+    assert_eq!(vary, vec!["Accept, Accept-Encoding"]);
+}
+
+#[test]
+fn vary_merges_headers_set_directly_and_respects_star() {
+    let merged = HTTPResponse::ok("hi").with_header("Vary", "Origin").vary("Accept-Encoding");
+    let star = HTTPResponse::ok("hi").with_header("Vary", "*").vary("Accept");
+
+    assert_eq!(merged.get_header("Vary"), Some("Origin, Accept-Encoding"));
+    assert_eq!(star.get_header("Vary"), Some("*"));
+}
+
+#[test]
+fn sse_events_are_framed_for_event_source() {
+    assert_eq!(SseEvent::data("hello").to_frame(), "data: hello\n\n");
+    assert_eq!(
+        SseEvent::data("line one\nline two").event("update").id("7").retry(3000).to_frame(),
+        "event: update\nid: 7\nretry: 3000\ndata: line one\ndata: line two\n\n"
+    );
+}
+
+#[test]
+fn sse_data_with_a_lone_cr_cannot_inject_fields() {
+    assert_eq!(SseEvent::data("x\revent: admin").to_frame(), "data: x\ndata: event: admin\n\n");
+    assert_eq!(SseEvent::data("a\r\nb\rc\nd").to_frame(), "data: a\ndata: b\ndata: c\ndata: d\n\n");
+}
+
+#[tokio::test]
+async fn event_stream_sends_each_event_as_a_data_frame() {
+    let router = Router::new("/").get("/events", |_req: HTTPRequest| {
+        let (sender, receiver) = tokio::sync::mpsc::channel(4);
+        tokio::spawn(async move {
+            let _ = sender.send(SseEvent::data("first")).await;
+            let _ = sender.send("second".into()).await;
+        });
+        HTTPResponse::event_stream(receiver)
+    }, vec![]);
+    let server = TestServer::start(HTTPServer::new("127.0.0.1:0").add_router(router)).await;
+
+    let res = server.get("/events").await;
+    let wire = res.text();
+
+    assert_eq!(res.status, 200);
+    assert_eq!(res.header("Content-Type"), Some("text/event-stream"));
+    assert_eq!(res.header("Cache-Control"), Some("no-cache"));
+    assert_eq!(res.header("Transfer-Encoding"), Some("chunked"));
+    assert_eq!(wire.matches("data: ").count(), 2);
+    assert!(wire.contains("data: first\n\n") && wire.contains("data: second\n\n"));
+    assert!(wire.find("first") < wire.find("second"));
+    assert!(wire.ends_with("0\r\n\r\n"));
+}
@@ -0,0 +1,386 @@
+mod common;
+
+use common::TestServer;
+use http_server_from_scratch::requests::HTTPRequest;
+use http_server_from_scratch::responses::{HTTPResponse, Json};
+use http_server_from_scratch::routing::{Cors, JwtAlgorithm, JwtAuth, JwtClaims, MiddlewareStack, Router};
+use http_server_from_scratch::server::HTTPServer;
+
+fn user_routes() -> Router {
+    Router::new("/api")
+        .get("/users", |_req: HTTPRequest| HTTPResponse::ok("users"), vec![])
+}
+
+fn order_routes() -> Router {
+    Router::new("/api/")
+        .get("/orders", |_req: HTTPRequest| HTTPResponse::ok("orders"), vec![])
+        .add_response_middleware(|_req: &HTTPRequest, res: HTTPResponse| res.with_header("X-Orders", "seen"))
+}
+
+#[tokio::test]
+async fn merged_routers_serve_routes_from_both() {
+    let api = user_routes().merge(order_routes());
+    let server = TestServer::start(HTTPServer::new("127.0.0.1:0").add_router(api)).await;
+
+    let users = server.get("/api/users").await;
+    let orders = server.get("/api/orders").await;
+
+    assert_eq!(users.status, 200);
+    assert_eq!(users.text(), "users");
+    assert_eq!(orders.status, 200);
+    assert_eq!(orders.text(), "orders");
+    // The merged router's middleware now covers every route
+    assert_eq!(users.header("X-Orders"), Some("seen"));
+}
+
+#[test]
+#[should_panic(expected = "prefixes differ")]
+fn merging_different_prefixes_panics() {
+    let _ = user_routes().merge(Router::new("/admin"));
+}
+
+#[derive(serde::Deserialize)]
+struct NewUser {
+    name: String,
+}
+
+fn create(req: HTTPRequest) -> Result<HTTPResponse, HTTPResponse> {
+    let user: NewUser = req.body()?;
+    if user.name.is_empty() {
+        return Err(HTTPResponse::new(422, "Name is required"));
+    }
+    Ok(HTTPResponse::created(&format!("Created {}", user.name)))
+}
+
+#[tokio::test]
+async fn result_handlers_turn_errors_into_responses() {
+    let router = Router::new("/")
+        .post("/users", create, vec![])
+        .get_async("/async", |req: HTTPRequest| async move {
+            let page: u32 = req.query_params_as::<std::collections::HashMap<String, u32>>()?
+                .get("page")
+                .copied()
+                .unwrap_or(1);
+            Ok::<_, HTTPResponse>(HTTPResponse::ok(&format!("page {}", page)))
+        }, vec![]);
+    let server = TestServer::start(HTTPServer::new("127.0.0.1:0").add_router(router)).await;
+
+    let created = server.request("POST", "/users", &[], br#"{"name":"Ann"}"#).await;
+    let bad_body = server.request("POST", "/users", &[], b"not json").await;
+    let rejected = server.request("POST", "/users", &[], br#"{"name":""}"#).await;
+    let async_ok = server.get("/async?page=2").await;
+    let async_bad = server.get("/async?page=two").await;
+
+    assert_eq!(created.status, 201);
+    assert_eq!(created.text(), "Created Ann");
+    assert_eq!(bad_body.status, 400);
+    assert!(bad_body.text().starts_with("Failed to deserialize request body"));
+    assert_eq!(rejected.status, 422);
+    assert_eq!(async_ok.text(), "page 2");
+    assert_eq!(async_bad.status, 400);
+}
+
+#[tokio::test]
+async fn handlers_can_return_plain_values() {
+    let router = Router::new("/")
+        .get("/text", |_req: HTTPRequest| "pong", vec![])
+        .get("/owned", |req: HTTPRequest| format!("hello {}", req.query("name", "world")), vec![])
+        .post("/jobs", |_req: HTTPRequest| (201, "done"), vec![])
+        .get("/json", |_req: HTTPRequest| Json(vec!["a", "b"]), vec![]);
+    let server = TestServer::start(HTTPServer::new("127.0.0.1:0").add_router(router)).await;
+
+    let text = server.get("/text").await;
+    let owned = server.get("/owned?name=Ann").await;
+    let created = server.request("POST", "/jobs", &[], b"").await;
+    let json = server.get("/json").await;
+
+    assert_eq!((text.status, text.text().as_str()), (200, "pong"));
+    assert_eq!((owned.status, owned.text().as_str()), (200, "hello Ann"));
+    assert_eq!((created.status, created.text().as_str()), (201, "done"));
+    assert_eq!(json.header("Content-Type"), Some("application/json"));
+    assert_eq!(json.text(), r#"["a","b"]"#);
+}
+
+fn require_token(req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
+    match req.get_header("X-Token") {
+        Some(_) => Ok(req),
+        None => Err(HTTPResponse::unauthorized("Missing token")),
+    }
+}
+
+struct Checked;
+
+fn mark_checked(mut req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
+    req.insert_extension(Checked);
+    Ok(req)
+}
+
+fn show_trail(req: HTTPRequest) -> &'static str {
+    if req.extension::<Checked>().is_some() { "checked" } else { "unchecked" }
+}
+
+#[tokio::test]
+async fn middleware_stack_applies_to_every_route_it_is_given() {
+    let protected = MiddlewareStack::new().then(require_token).then(mark_checked);
+    let router = Router::new("/")
+        .get_with("/reports", show_trail, &protected)
+        .delete_with("/reports", show_trail, &protected)
+        .get("/public", show_trail, vec![]);
+    let server = TestServer::start(HTTPServer::new("127.0.0.1:0").add_router(router)).await;
+
+    let anonymous = server.get("/reports").await;
+    let listed = server.request("GET", "/reports", &[("X-Token", "abc")], b"").await;
+    let deleted = server.request("DELETE", "/reports", &[("X-Token", "abc")], b"").await;
+    let public = server.get("/public").await;
+
+    assert_eq!(anonymous.status, 401);
+    assert_eq!(listed.text(), "checked");
+    assert_eq!(deleted.text(), "checked");
+    assert_eq!(public.text(), "unchecked");
+}
+
+#[tokio::test]
+async fn single_route_middleware_needs_no_annotation() {
+    let router = Router::new("/").get("/reports", show_trail, vec![require_token]);
+    let server = TestServer::start(HTTPServer::new("127.0.0.1:0").add_router(router)).await;
+
+    let res = server.get("/reports").await;
+
+    assert_eq!(res.status, 401);
+}
+
+#[tokio::test]
+async fn messy_paths_match_the_normalized_route() {
+    let router = Router::new("/api")
+        .get("/users", |_req: HTTPRequest| "users", vec![]);
+    let server = TestServer::start(HTTPServer::new("127.0.0.1:0").add_router(router)).await;
+
+    for path in ["/api//users", "/api/./users", "/api/x/../users", "/../api/users"] {
+        let res = server.get(path).await;
+        assert_eq!((res.status, res.text().as_str()), (200, "users"), "GET {}", path);
+    }
+}
+
+fn named_routes() -> Router {
+    let admin = Router::new("/admin")
+        .get("/", |_req: HTTPRequest| "dashboard", vec![]).name("admin_home")
+        .get("/files/{*path}", |_req: HTTPRequest| "file", vec![]).name("admin_file");
+
+    Router::new("/api")
+        .get("/users/{id}", |_req: HTTPRequest| "user", vec![]).name("user_detail")
+        .get("/orders/{id:\\d+}", |_req: HTTPRequest| "order", vec![]).name("order_detail")
+        .mount(admin)
+}
+
+#[test]
+fn url_for_fills_in_route_params() {
+    let router = named_routes();
+
+    assert_eq!(router.url_for("user_detail", &[("id", "42")]), Ok("/api/users/42".to_string()));
+    assert_eq!(router.url_for("user_detail", &[("id", "ann smith")]), Ok("/api/users/ann%20smith".to_string()));
+    assert_eq!(router.url_for("admin_home", &[]), Ok("/api/admin".to_string()));
+    assert_eq!(router.url_for("admin_file", &[("path", "a/b c.txt")]), Ok("/api/admin/files/a/b%20c.txt".to_string()));
+}
+
+#[test]
+fn url_for_puts_extra_params_in_the_query_string() {
+    let router = named_routes();
+
+    let url = router.url_for("user_detail", &[("id", "42"), ("tab", "posts & likes")]);
+
+    assert_eq!(url, Ok("/api/users/42?tab=posts+%26+likes".to_string()));
+}
+
+#[test]
+fn url_for_rejects_unknown_names_and_bad_params() {
+    let router = named_routes();
+
+    assert_eq!(router.url_for("nope", &[]), Err("No route named 'nope'".to_string()));
+    assert_eq!(router.url_for("user_detail", &[]), Err("Missing parameter 'id' for route 'user_detail'".to_string()));
+    assert!(router.url_for("order_detail", &[("id", "abc")]).is_err());
+}
+
+#[test]
+fn describe_lists_routes_with_their_middleware() {
+    let admin = Router::new("/admin")
+        .add_middleware(mark_checked)
+        .get("/stats", show_trail, vec![require_token]).name("admin_stats");
+    let api = Router::new("/api")
+        .add_middleware(require_token)
+        .get("/users", show_trail, vec![])
+        .post("/users", show_trail, vec![])
+        .any("/echo", show_trail, vec![])
+        .mount(admin);
+    let server = HTTPServer::new("127.0.0.1:0")
+        .add_middleware(|req: HTTPRequest| Ok::<_, HTTPResponse>(req))
+        .add_router(api)
+        .add_router_for_host("www.localhost", Router::new("/").get("/", show_trail, vec![]))
+        .health_endpoint("/healthz");
+
+    let routes: Vec<(String, String, Option<String>)> = server.describe().into_iter()
+        .map(|info| (info.method, info.path, info.host))
+        .collect();
+    let stats = server.describe().into_iter().find(|info| info.path == "/api/admin/stats").unwrap();
+
+    assert_eq!(routes, vec![
+        ("GET".to_string(), "/healthz".to_string(), None),
+        ("GET".to_string(), "/".to_string(), Some("www.localhost".to_string())),
+        ("GET".to_string(), "/api/users".to_string(), None),
+        ("POST".to_string(), "/api/users".to_string(), None),
+        ("*".to_string(), "/api/echo".to_string(), None),
+        ("GET".to_string(), "/api/admin/stats".to_string(), None),
+    ]);
+    assert_eq!(stats.middleware, vec!["describe_lists_routes_with_their_middleware (closure)", "require_token", "mark_checked"]);
+    assert_eq!(stats.route_middleware, 1);
+    assert_eq!(stats.name.as_deref(), Some("admin_stats"));
+    assert_eq!(
+        stats.to_string(),
+        "GET     /api/admin/stats  [describe_lists_routes_with_their_middleware (closure) → require_token → mark_checked] + 1 route middleware"
+    );
+}
+
+fn cors_server() -> HTTPServer {
+    let cors = Cors::new()
+        .allow_origins(["https://app.example.com", "https://admin.example.com"])
+        .allow_origin_fn(|origin| origin.ends_with(".preview.example.com"))
+        .allow_methods(["GET", "POST"]);
+    HTTPServer::new("127.0.0.1:0")
+        .add_middleware(cors.preflight())
+        .add_response_middleware(cors.headers())
+        .add_router(Router::new("/").get("/data", |_req: HTTPRequest| HTTPResponse::ok("data"), vec![]))
+}
+
+#[tokio::test]
+async fn cors_echoes_only_allowed_origins() {
+    let server = TestServer::start(cors_server()).await;
+
+    let listed = server.request("GET", "/data", &[("Origin", "https://admin.example.com")], b"").await;
+    let matched = server.request("GET", "/data", &[("Origin", "https://pr-7.preview.example.com")], b"").await;
+    let other = server.request("GET", "/data", &[("Origin", "https://evil.example.net")], b"").await;
+
+    assert_eq!(listed.header("Access-Control-Allow-Origin"), Some("https://admin.example.com"));
+    assert_eq!(listed.header("Vary"), Some("Origin"));
+    assert_eq!(matched.header("Access-Control-Allow-Origin"), Some("https://pr-7.preview.example.com"));
+    assert_eq!(other.status, 200);
+    assert_eq!(other.header("Access-Control-Allow-Origin"), None);
+}
+
+#[tokio::test]
+async fn cors_preflight_from_a_disallowed_origin_gets_no_cors_headers() {
+    let server = TestServer::start(cors_server()).await;
+    let preflight = |origin| [("Origin", origin), ("Access-Control-Request-Method", "POST")];
+
+    let allowed = server.request("OPTIONS", "/data", &preflight("https://app.example.com"), b"").await;
+    let denied = server.request("OPTIONS", "/data", &preflight("https://evil.example.net"), b"").await;
+
+    assert_eq!(allowed.status, 204);
+    assert_eq!(allowed.header("Access-Control-Allow-Origin"), Some("https://app.example.com"));
+    assert_eq!(allowed.header("Access-Control-Allow-Methods"), Some("GET, POST"));
+    assert_eq!(denied.status, 403);
+    assert_eq!(denied.header("Access-Control-Allow-Origin"), None);
+    assert_eq!(denied.header("Access-Control-Allow-Methods"), None);
+}
+
+#[tokio::test]
+async fn cors_leaves_same_origin_requests_alone() {
+    let server = TestServer::start(cors_server()).await;
+
+    let res = server.get("/data").await;
+
+    assert_eq!(res.status, 200);
+    assert_eq!(res.text(), "data");
+    assert_eq!(res.header("Access-Control-Allow-Origin"), None);
+    assert_eq!(res.header("Vary"), None);
+}
+
+fn jwt_auth() -> JwtAuth {
+    JwtAuth::new("test-secret").algorithm(JwtAlgorithm::HS256)
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+}
+
+async fn jwt_server() -> TestServer {
+    let router = Router::new("/")
+        .add_middleware(jwt_auth().middleware())
+        .get("/me", |req: HTTPRequest| {
+            let claims = req.extension::<JwtClaims>().expect("claims set by JwtAuth");
+            HTTPResponse::ok(&format!("{} ({})", claims.subject().unwrap_or("?"), claims.get_str("role").unwrap_or("?")))
+        }, vec![]);
+    TestServer::start(HTTPServer::new("127.0.0.1:0").add_router(router)).await
+}
+
+async fn get_with_token(server: &TestServer, token: &str) -> common::TestResponse {
+    let authorization = format!("Bearer {}", token);
+    server.request("GET", "/me", &[("Authorization", &authorization)], b"").await
+}
+
+#[tokio::test]
+async fn jwt_auth_hands_verified_claims_to_the_handler() {
+    let server = jwt_server().await;
+    let token = jwt_auth().encode(&serde_json::json!({ "sub": "ann", "role": "editor", "exp": unix_now() + 60 })).unwrap();
+
+    let res = get_with_token(&server, &token).await;
+
+    assert_eq!(res.status, 200);
+    assert_eq!(res.text(), "ann (editor)");
+}
+
+#[tokio::test]
+async fn jwt_auth_rejects_expired_tokens() {
+    let server = jwt_server().await;
+    let token = jwt_auth().encode(&serde_json::json!({ "sub": "ann", "exp": unix_now() - 1 })).unwrap();
+
+    let res = get_with_token(&server, &token).await;
+
+    assert_eq!(res.status, 401);
+    assert_eq!(res.text(), "Token expired");
+    assert_eq!(res.header("WWW-Authenticate"), Some("Bearer"));
+}
+
+#[tokio::test]
+async fn jwt_auth_rejects_tampered_and_malformed_tokens() {
+    let server = jwt_server().await;
+    let token = jwt_auth().encode(&serde_json::json!({ "sub": "ann", "exp": unix_now() + 60 })).unwrap();
+    let (signing_input, _) = token.rsplit_once('.').unwrap();
+    let forged = JwtAuth::new("wrong-secret").encode(&serde_json::json!({ "sub": "ann", "exp": unix_now() + 60 })).unwrap();
+    let forged_signature = forged.rsplit_once('.').unwrap().1;
+
+    let tampered = get_with_token(&server, &format!("{}.{}", signing_input, forged_signature)).await;
+    let malformed = get_with_token(&server, "not-a-jwt").await;
+    let missing = server.get("/me").await;
+
+    assert_eq!(tampered.status, 401);
+    assert_eq!(tampered.text(), "Invalid token signature");
+    assert_eq!(malformed.status, 401);
+    assert_eq!(malformed.text(), "Malformed token");
+    assert_eq!(missing.status, 401);
+    assert_eq!(missing.text(), "Missing bearer token");
+}
+
+#[test]
+fn jwt_auth_checks_the_algorithm_and_signature_of_known_tokens() {
+    // The well-known example token from jwt.io (it has no "exp")
+    let example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.\
+        eyJzdWIiOiIxMjM0NTY3ODkwIiwibmFtZSI6IkpvaG4gRG9lIiwiaWF0IjoxNTE2MjM5MDIyfQ.\
+        SflKxwRJSMeKKF2QT4fwpMeJf36POk6yJV_adQssw5c";
+    let unsigned = "eyJhbGciOiJub25lIn0.eyJzdWIiOiJhbm4iLCJleHAiOjk5OTk5OTk5OTl9.";
+
+    assert_eq!(JwtAuth::new("your-256-bit-secret").verify(example), Err("Token has no expiry".to_string()));
+    assert_eq!(JwtAuth::new("some-other-secret").verify(example), Err("Invalid token signature".to_string()));
+    assert_eq!(jwt_auth().verify(unsigned), Err("Token must be signed with HS256".to_string()));
+}
+
+#[test]
+fn jwt_auth_can_be_checked_against_a_built_request() {
+    let token = jwt_auth().encode(&serde_json::json!({ "sub": "root", "role": "admin", "exp": unix_now() + 60 })).unwrap();
+    let signed_in = HTTPRequest::builder().path("/admin").header("Authorization", &format!("Bearer {}", token)).build();
+    let anonymous = HTTPRequest::builder().path("/admin").build();
+
+    let req = jwt_auth().check(signed_in).expect("valid token");
+    let rejected = jwt_auth().check(anonymous).unwrap_err();
+
+    assert_eq!(req.extension::<JwtClaims>().and_then(|claims| claims.get_str("role")), Some("admin"));
+    assert_eq!(rejected.status.code(), 401);
+}
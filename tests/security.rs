@@ -0,0 +1,43 @@
+use http_server_from_scratch::security::{hmac_sha256, verify_hmac_sha256};
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// RFC 4231 section 4 (case 5, a truncated tag, doesn't apply)
+#[test]
+fn hmac_sha256_matches_the_rfc_4231_test_vectors() {
+    let key_25_bytes: Vec<u8> = (0x01..=0x19).collect();
+    let cases: [(Vec<u8>, Vec<u8>, &str); 6] = [
+        (vec![0x0b; 20], b"Hi There".to_vec(),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"),
+        (b"Jefe".to_vec(), b"what do ya want for nothing?".to_vec(),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"),
+        (vec![0xaa; 20], vec![0xdd; 50],
+            "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe"),
+        (key_25_bytes, vec![0xcd; 50],
+            "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b"),
+        // Key longer than a 64-byte block
+        (vec![0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"),
+        // Long key and a message spanning several blocks
+        (vec![0xaa; 131], b"This is a test using a larger than block-size key and a larger than block-size data. The key needs to be hashed before being used by the HMAC algorithm.".to_vec(),
+            "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2"),
+    ];
+
+    for (key, message, expected) in &cases {
+        let signature = hmac_sha256(key, message);
+        assert_eq!(hex(&signature), *expected);
+        assert!(verify_hmac_sha256(key, message, &signature));
+    }
+}
+
+#[test]
+fn verify_hmac_sha256_rejects_altered_or_truncated_signatures() {
+    let mut signature = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+
+    assert!(!verify_hmac_sha256(b"Jefe", b"what do ya want for nothing?", &signature[..16]));
+    assert!(!verify_hmac_sha256(b"Jeff", b"what do ya want for nothing?", &signature));
+    signature[31] ^= 1;
+    assert!(!verify_hmac_sha256(b"Jefe", b"what do ya want for nothing?", &signature));
+}
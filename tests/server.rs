@@ -0,0 +1,611 @@
+mod common;
+
+use common::TestServer;
+use http_server_from_scratch::requests::HTTPRequest;
+use http_server_from_scratch::responses::{HTTPResponse, SseEvent};
+use http_server_from_scratch::routing::Router;
+use http_server_from_scratch::server::HTTPServer;
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+fn app() -> HTTPServer {
+    let router = Router::new("/")
+        .get("/about", |_req: HTTPRequest| HTTPResponse::ok("").with_html_body("<h1>About Us</h1>"), vec![]);
+
+    // Port 0: the OS picks a free port, so tests can run in parallel
+    HTTPServer::new("127.0.0.1:0").add_router(router)
+}
+
+#[tokio::test]
+async fn serves_about_page() {
+    let server = TestServer::start(app()).await;
+
+    let res = server.get("/about").await;
+
+    assert_eq!(res.status, 200);
+    assert_eq!(res.header("Content-Type"), Some("text/html"));
+    assert_eq!(res.text(), "<h1>About Us</h1>");
+}
+
+#[tokio::test]
+async fn unknown_path_is_not_found() {
+    let server = TestServer::start(app()).await;
+
+    let res = server.get("/missing").await;
+
+    assert_eq!(res.status, 404);
+}
+
+#[tokio::test]
+async fn bind_reports_the_assigned_port() {
+    let bound = app().bind().await.expect("bind to port 0");
+
+    let addr = bound.local_addr();
+
+    assert_eq!(addr.ip().to_string(), "127.0.0.1");
+    assert_ne!(addr.port(), 0);
+}
+
+#[tokio::test]
+async fn run_on_serves_a_caller_bound_listener() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind to port 0");
+    let addr = listener.local_addr().expect("read the bound address");
+    tokio::spawn(async move {
+        let _ = app().run_on(listener).await;
+    });
+
+    let mut stream = TcpStream::connect(addr).await.expect("connect");
+    stream.write_all(b"GET /about HTTP/1.1\r\nConnection: close\r\n\r\n").await.expect("send");
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.expect("read");
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+}
+
+#[tokio::test]
+async fn serve_connection_works_over_an_in_memory_stream() {
+    let (mut client, server_side) = tokio::io::duplex(4096);
+    tokio::spawn(async move {
+        let _ = app().serve_connection(server_side).await;
+    });
+
+    client.write_all(b"GET /about HTTP/1.1\r\nConnection: close\r\n\r\n").await.expect("send");
+    let mut response = String::new();
+    client.read_to_string(&mut response).await.expect("read");
+
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with("<h1>About Us</h1>"));
+}
+
+fn echo_length_app() -> HTTPServer {
+    HTTPServer::new("127.0.0.1:0")
+        .add_router(Router::new("/").post("/echo", |req: HTTPRequest| req.body_bytes().len().to_string(), vec![]))
+}
+
+#[tokio::test]
+async fn expect_100_continue_is_answered_before_the_body_is_read() {
+    let (mut client, server_side) = tokio::io::duplex(4096);
+    tokio::spawn(async move {
+        let _ = echo_length_app().serve_connection(server_side).await;
+    });
+
+    client.write_all(b"POST /echo HTTP/1.1\r\nContent-Length: 5\r\nExpect: 100-continue\r\nConnection: close\r\n\r\n").await.expect("send headers");
+    let mut interim = [0; 25];
+    tokio::time::timeout(Duration::from_secs(5), client.read_exact(&mut interim))
+        .await
+        .expect("interim response arrived before the body was sent")
+        .expect("read interim response");
+    client.write_all(b"hello").await.expect("send body");
+    let mut response = String::new();
+    client.read_to_string(&mut response).await.expect("read");
+
+    assert_eq!(&interim, b"HTTP/1.1 100 Continue\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with("\r\n\r\n5"));
+}
+
+#[tokio::test]
+async fn no_100_continue_when_the_body_is_buffered_or_the_client_is_http_1_0() {
+    let (mut buffered, server_side) = tokio::io::duplex(4096);
+    tokio::spawn(async move {
+        let _ = echo_length_app().serve_connection(server_side).await;
+    });
+    let (mut old_client, server_side) = tokio::io::duplex(4096);
+    tokio::spawn(async move {
+        let _ = echo_length_app().serve_connection(server_side).await;
+    });
+
+    buffered.write_all(b"POST /echo HTTP/1.1\r\nContent-Length: 5\r\nExpect: 100-continue\r\nConnection: close\r\n\r\nhello").await.expect("send");
+    let mut buffered_response = String::new();
+    buffered.read_to_string(&mut buffered_response).await.expect("read");
+
+    // HTTP/1.0 has no interim responses, so the body follows unprompted
+    old_client.write_all(b"POST /echo HTTP/1.0\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\r\n").await.expect("send headers");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    old_client.write_all(b"hello").await.expect("send body");
+    let mut old_response = String::new();
+    old_client.read_to_string(&mut old_response).await.expect("read");
+
+    assert!(buffered_response.starts_with("HTTP/1.1 200"));
+    assert!(!buffered_response.contains("100 Continue"));
+    assert!(old_response.starts_with("HTTP/1.1 200"));
+    assert!(!old_response.contains("100 Continue"));
+    assert!(old_response.ends_with("\r\n\r\n5"));
+}
+
+#[tokio::test]
+async fn default_headers_fill_in_missing_headers_only() {
+    let router = Router::new("/")
+        .get("/plain", |_req: HTTPRequest| HTTPResponse::ok("plain"), vec![])
+        .get("/framed", |_req: HTTPRequest| HTTPResponse::ok("framed").with_header("X-Frame-Options", "SAMEORIGIN"), vec![]);
+    let app = HTTPServer::new("127.0.0.1:0")
+        .add_router(router)
+        .default_header("Server", "myapp")
+        .default_header("X-Frame-Options", "DENY");
+    let server = TestServer::start(app).await;
+
+    let plain = server.get("/plain").await;
+    let framed = server.get("/framed").await;
+    let missing = server.get("/missing").await;
+
+    assert_eq!(plain.header("Server"), Some("myapp"));
+    assert_eq!(plain.header("X-Frame-Options"), Some("DENY"));
+    let frame_options: Vec<&str> = framed.headers.iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("X-Frame-Options"))
+        .map(|(_, value)| value.as_str())
+        .collect();
+    assert_eq!(frame_options, vec!["SAMEORIGIN"]);
+    assert_eq!(framed.header("Server"), Some("myapp"));
+    assert_eq!(missing.status, 404);
+    assert_eq!(missing.header("Server"), Some("myapp"));
+}
+
+async fn panic_after_yielding(_req: HTTPRequest) -> HTTPResponse {
+    tokio::task::yield_now().await;
+    panic!("async handler blew up")
+}
+
+#[tokio::test]
+async fn panicking_handler_returns_500_and_server_keeps_serving() {
+    let router = Router::new("/")
+        .get("/boom", |_req: HTTPRequest| -> HTTPResponse { panic!("handler blew up") }, vec![])
+        .get_async("/boom-later", panic_after_yielding, vec![])
+        .get("/about", |_req: HTTPRequest| HTTPResponse::ok("still here"), vec![]);
+    let server = TestServer::start(HTTPServer::new("127.0.0.1:0").add_router(router)).await;
+
+    let sync_panic = server.get("/boom").await;
+    let async_panic = server.get("/boom-later").await;
+    let after = server.get("/about").await;
+
+    assert_eq!(sync_panic.status, 500);
+    assert_eq!(sync_panic.text(), "Internal Server Error");
+    assert_eq!(async_panic.status, 500);
+    assert_eq!(after.status, 200);
+    assert_eq!(after.text(), "still here");
+}
+
+// run_blocking binds `addr` itself, so the test picks a free port up front
+// and retries the connect until the server is listening
+fn connect_when_ready(addr: SocketAddr) -> std::net::TcpStream {
+    for _ in 0..50 {
+        if let Ok(stream) = std::net::TcpStream::connect(addr) {
+            return stream;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    panic!("server at {} never started listening", addr);
+}
+
+#[test]
+fn run_blocking_serves_on_its_own_runtime() {
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let server = HTTPServer::new(&addr.to_string())
+        .add_router(Router::new("/").get("/about", |_req: HTTPRequest| "about", vec![]))
+        .workers(2);
+    std::thread::spawn(move || server.run_blocking().expect("run_blocking"));
+
+    let mut stream = connect_when_ready(addr);
+    stream.write_all(b"GET /about HTTP/1.1\r\nConnection: close\r\n\r\n").expect("send");
+    let mut response = String::new();
+    stream.read_to_string(&mut response).expect("read");
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("\r\n\r\nabout"));
+}
+
+#[test]
+#[should_panic(expected = "at least one thread")]
+fn zero_workers_is_rejected() {
+    let _ = HTTPServer::new("127.0.0.1:0").workers(0);
+}
+
+#[tokio::test]
+async fn body_shorter_than_content_length_is_rejected() {
+    let app = HTTPServer::new("127.0.0.1:0")
+        .add_router(Router::new("/").post("/echo", |req: HTTPRequest| req.body_bytes().len().to_string(), vec![]))
+        .read_timeout(Duration::from_millis(300));
+    let server = TestServer::start(app).await;
+
+    // Client stalls mid-body: refused once the read timeout passes
+    let mut stalled = server.connect().await;
+    stalled.write_all(b"POST /echo HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc").await.expect("send");
+    let mut response = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), stalled.read_to_end(&mut response))
+        .await
+        .expect("server answered within the read timeout")
+        .expect("read");
+    let stalled = common::TestResponse::parse(&response);
+
+    // Client hangs up its write side mid-body
+    let mut truncated = server.connect().await;
+    truncated.write_all(b"POST /echo HTTP/1.1\r\nContent-Length: 10\r\n\r\nabcd").await.expect("send");
+    truncated.shutdown().await.expect("half-close");
+    let mut response = Vec::new();
+    truncated.read_to_end(&mut response).await.expect("read");
+    let truncated = common::TestResponse::parse(&response);
+
+    assert_eq!(stalled.status, 400);
+    assert_eq!(stalled.text(), "Bad Request: body has 3 of the 10 bytes declared by Content-Length");
+    assert_eq!(stalled.header("Connection"), Some("close"));
+    assert_eq!(truncated.status, 400);
+    assert_eq!(truncated.text(), "Bad Request: body has 4 of the 10 bytes declared by Content-Length");
+}
+
+#[tokio::test]
+async fn content_length_with_transfer_encoding_is_rejected() {
+    let app = HTTPServer::new("127.0.0.1:0")
+        .add_router(Router::new("/").post("/echo", |req: HTTPRequest| req.body_bytes().len().to_string(), vec![]));
+    let server = TestServer::start(app).await;
+
+    let mut stream = server.connect().await;
+    stream.write_all(
+        b"POST /echo HTTP/1.1\r\nContent-Length: 4\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\nGET /about HTTP/1.1\r\n\r\n"
+    ).await.expect("send");
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.expect("read");
+    let res = common::TestResponse::parse(&response);
+
+    // One 400 and a closed connection: the smuggled GET is never answered
+    assert_eq!(res.status, 400);
+    assert_eq!(res.text(), "Bad Request: both Content-Length and Transfer-Encoding were sent");
+    assert_eq!(res.header("Connection"), Some("close"));
+}
+
+// Sends `raw` on a fresh connection and reads until the server hangs up
+async fn send_raw(server: &TestServer, raw: &[u8]) -> (common::TestResponse, usize) {
+    let mut stream = server.connect().await;
+    stream.write_all(raw).await.expect("send");
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.expect("read");
+    let answered = response.windows(9).filter(|window| window == b"HTTP/1.1 ").count();
+    (common::TestResponse::parse(&response), answered)
+}
+
+#[tokio::test]
+async fn transfer_encoding_not_ending_in_chunked_is_rejected() {
+    let app = HTTPServer::new("127.0.0.1:0")
+        .add_router(Router::new("/").post("/echo", |req: HTTPRequest| req.body_bytes().len().to_string(), vec![]));
+    let server = TestServer::start(app).await;
+
+    let (gzip, gzip_answered) = send_raw(&server,
+        b"POST /echo HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\nGET /about HTTP/1.1\r\n\r\n").await;
+    let (chunked_then_gzip, _) = send_raw(&server,
+        b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked, gzip\r\n\r\n0\r\n\r\n").await;
+    let (unknown, _) = send_raw(&server,
+        b"POST /echo HTTP/1.1\r\nTransfer-Encoding: xchunked\r\n\r\n0\r\n\r\n").await;
+
+    // The smuggled GET is never answered
+    assert_eq!(gzip.status, 400);
+    assert_eq!(gzip.text(), "Bad Request: Transfer-Encoding must end with chunked");
+    assert_eq!(gzip.header("Connection"), Some("close"));
+    assert_eq!(gzip_answered, 1);
+    assert_eq!(chunked_then_gzip.status, 400);
+    assert_eq!(unknown.status, 501);
+    assert_eq!(unknown.text(), "Not Implemented: unsupported Transfer-Encoding xchunked");
+}
+
+#[tokio::test]
+async fn conflicting_content_lengths_are_rejected() {
+    let app = HTTPServer::new("127.0.0.1:0")
+        .add_router(Router::new("/").post("/echo", |req: HTTPRequest| req.body_bytes().len().to_string(), vec![]));
+    let server = TestServer::start(app).await;
+
+    let (repeated, _) = send_raw(&server,
+        b"POST /echo HTTP/1.1\r\nContent-Length: 4\r\nContent-Length: 0\r\n\r\nabcd").await;
+    let (listed, _) = send_raw(&server,
+        b"POST /echo HTTP/1.1\r\nContent-Length: 4, 0\r\n\r\nabcd").await;
+    let (agreeing, _) = send_raw(&server,
+        b"POST /echo HTTP/1.1\r\nContent-Length: 4\r\nContent-Length: 4, 4\r\nConnection: close\r\n\r\nabcd").await;
+
+    assert_eq!(repeated.status, 400);
+    assert_eq!(repeated.text(), "Bad Request: conflicting Content-Length values");
+    assert_eq!(listed.status, 400);
+    assert_eq!(agreeing.status, 200);
+    assert_eq!(agreeing.text(), "4");
+}
+
+fn require_api_key(req: HTTPRequest) -> Result<HTTPRequest, HTTPResponse> {
+    match req.get_header("X-API-Key") {
+        Some(_) => Ok(req),
+        None => Err(HTTPResponse::unauthorized("Missing API key")),
+    }
+}
+
+#[tokio::test]
+async fn health_endpoint_skips_server_middleware() {
+    let app = HTTPServer::new("127.0.0.1:0")
+        .add_middleware(require_api_key)
+        .add_router(Router::new("/").get("/about", |_req: HTTPRequest| "about", vec![]))
+        .health_endpoint("/healthz");
+    let server = TestServer::start(app).await;
+
+    let health = server.get("/healthz").await;
+    let head = server.request("HEAD", "/healthz", &[], b"").await;
+    let about = server.get("/about").await;
+
+    assert_eq!(health.status, 200);
+    assert_eq!(health.text(), "OK");
+    assert_eq!(head.status, 200);
+    assert_eq!(about.status, 401);
+}
+
+#[tokio::test]
+async fn pipelined_requests_are_answered_in_order() {
+    let router = Router::new("/")
+        .get("/first", |_req: HTTPRequest| "first", vec![])
+        .get("/second", |_req: HTTPRequest| "second", vec![])
+        .post("/echo", |req: HTTPRequest| String::from_utf8_lossy(req.body_bytes()).into_owned(), vec![]);
+    let server = TestServer::start(HTTPServer::new("127.0.0.1:0").add_router(router)).await;
+
+    // Three requests in a single write, the middle one with a body
+    let mut stream = server.connect().await;
+    stream.write_all(concat!(
+        "GET /first HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello",
+        "GET /second HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    ).as_bytes()).await.expect("send");
+
+    let first = common::TestResponse::read_from(&mut stream).await;
+    let echo = common::TestResponse::read_from(&mut stream).await;
+    let second = common::TestResponse::read_from(&mut stream).await;
+
+    assert_eq!((first.status, first.text().as_str()), (200, "first"));
+    assert_eq!((echo.status, echo.text().as_str()), (200, "hello"));
+    assert_eq!((second.status, second.text().as_str()), (200, "second"));
+    assert_eq!(second.header("Connection"), Some("keep-alive"));
+}
+
+fn html_error(code: u16, message: &str) -> HTTPResponse {
+    HTTPResponse::ok("").with_html_body(&format!("<h1>{}</h1><p>{}</p>", code, message))
+}
+
+#[tokio::test]
+async fn error_page_restyles_the_servers_own_errors() {
+    let router = Router::new("/")
+        .get("/about", |_req: HTTPRequest| "about", vec![])
+        .get("/boom", |_req: HTTPRequest| -> HTTPResponse { panic!("handler blew up") }, vec![])
+        .get("/gone", |_req: HTTPRequest| HTTPResponse::not_found("Deleted by its owner"), vec![]);
+    let app = HTTPServer::new("127.0.0.1:0")
+        .add_router(router)
+        .error_page(404..=405, html_error)
+        .error_page(500..=599, |code: u16, _message: &str| (code, "Something broke"));
+    let server = TestServer::start(app).await;
+
+    let missing = server.get("/missing").await;
+    let wrong_method = server.request("POST", "/about", &[], b"").await;
+    let panicked = server.get("/boom").await;
+    let handler_404 = server.get("/gone").await;
+
+    assert_eq!(missing.status, 404);
+    assert_eq!(missing.header("Content-Type"), Some("text/html"));
+    assert_eq!(missing.text(), "<h1>404</h1><p>No router matched this path</p>");
+    assert_eq!(wrong_method.status, 405);
+    assert_eq!(wrong_method.header("Allow"), Some("GET"));
+    assert_eq!(wrong_method.text(), "<h1>405</h1><p>Method Not Allowed</p>");
+    assert_eq!(panicked.status, 500);
+    assert_eq!(panicked.text(), "Something broke");
+    // A 404 the handler chose is its own business
+    assert_eq!(handler_404.text(), "Deleted by its owner");
+}
+
+#[tokio::test]
+async fn socket_options_are_applied_to_accepted_connections() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind to port 0");
+    let _client = TcpStream::connect(listener.local_addr().unwrap()).await.expect("connect");
+    let (accepted, _) = listener.accept().await.expect("accept");
+
+    HTTPServer::new("127.0.0.1:0").apply_socket_options(&accepted).unwrap();
+    assert!(!accepted.nodelay().unwrap());
+    assert!(!socket2::SockRef::from(&accepted).keepalive().unwrap());
+
+    let tuned = HTTPServer::new("127.0.0.1:0")
+        .tcp_nodelay(true)
+        .tcp_keepalive(Duration::from_secs(60));
+    tuned.apply_socket_options(&accepted).unwrap();
+    assert!(accepted.nodelay().unwrap());
+    assert!(socket2::SockRef::from(&accepted).keepalive().unwrap());
+}
+
+async fn slow(req: HTTPRequest) -> HTTPResponse {
+    tokio::time::sleep(Duration::from_millis(req.query_u64("ms", 0))).await;
+    HTTPResponse::ok("done")
+}
+
+// A bound server with a 300ms shutdown grace period, plus the trigger and the
+// task running it
+async fn draining_server() -> (SocketAddr, tokio::sync::oneshot::Sender<()>, tokio::task::JoinHandle<()>) {
+    let router = Router::new("/").get_async("/slow", slow, vec![]);
+    let bound = HTTPServer::new("127.0.0.1:0")
+        .add_router(router)
+        .shutdown_timeout(Duration::from_millis(300))
+        .bind().await.expect("bind");
+    let addr = bound.local_addr();
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let running = tokio::spawn(async move {
+        bound.run_until(async { let _ = stop_rx.await; }).await.expect("serve");
+    });
+    (addr, stop_tx, running)
+}
+
+#[tokio::test]
+async fn shutdown_lets_in_flight_requests_finish_within_the_grace_period() {
+    let (addr, stop, running) = draining_server().await;
+    let mut busy = TcpStream::connect(addr).await.expect("connect");
+    let mut idle = TcpStream::connect(addr).await.expect("connect");
+    busy.write_all(b"GET /slow?ms=100 HTTP/1.1\r\nHost: localhost\r\n\r\n").await.expect("send");
+    tokio::time::sleep(Duration::from_millis(30)).await;
+
+    let started = std::time::Instant::now();
+    stop.send(()).expect("trigger shutdown");
+    let res = common::TestResponse::read_from(&mut busy).await;
+    let mut leftover = Vec::new();
+    idle.read_to_end(&mut leftover).await.expect("idle connection closed");
+    running.await.expect("server task");
+
+    assert_eq!(res.status, 200);
+    assert_eq!(res.text(), "done");
+    assert_eq!(res.header("Connection"), Some("close"));
+    assert!(leftover.is_empty());
+    assert!(started.elapsed() < Duration::from_millis(300), "took {:?}", started.elapsed());
+}
+
+#[tokio::test]
+async fn shutdown_closes_requests_that_outlast_the_grace_period() {
+    let (addr, stop, running) = draining_server().await;
+    let mut stuck = TcpStream::connect(addr).await.expect("connect");
+    stuck.write_all(b"GET /slow?ms=5000 HTTP/1.1\r\nHost: localhost\r\n\r\n").await.expect("send");
+    tokio::time::sleep(Duration::from_millis(30)).await;
+
+    let started = std::time::Instant::now();
+    stop.send(()).expect("trigger shutdown");
+    running.await.expect("server task");
+    let mut response = Vec::new();
+    let _ = stuck.read_to_end(&mut response).await;
+
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(300) && elapsed < Duration::from_secs(2), "took {:?}", elapsed);
+    assert!(response.is_empty());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unix_socket_serves_until_shutdown_and_removes_the_socket_file() {
+    let path = std::env::temp_dir().join(format!("http-server-test-{}.sock", std::process::id()));
+    let socket = path.to_str().expect("utf-8 temp path").to_string();
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let running = tokio::spawn(async move {
+        app().run_unix_until(&socket, async { let _ = stop_rx.await; }).await.expect("serve");
+    });
+
+    let mut stream = loop {
+        match tokio::net::UnixStream::connect(&path).await {
+            Ok(stream) => break stream,
+            Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+        }
+    };
+    stream.write_all(b"GET /about HTTP/1.1\r\nConnection: close\r\n\r\n").await.expect("send");
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.expect("read");
+    stop_tx.send(()).expect("trigger shutdown");
+    running.await.expect("server task");
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn compression_skips_small_bodies_and_types_off_the_allowlist() {
+    let page = format!("<ul>{}</ul>", "<li>item</li>".repeat(200));
+    let png = vec![0x89; 4096];
+    let router = Router::new("/")
+        .get("/tiny.json", |_req: HTTPRequest| HTTPResponse::ok("").with_bytes_body(b"{\"ok\":1}".to_vec(), "application/json"), vec![])
+        .get("/page", move |_req: HTTPRequest| HTTPResponse::ok("").with_html_body(&page), vec![])
+        .get("/logo.png", move |_req: HTTPRequest| HTTPResponse::ok("").with_bytes_body(png.clone(), "image/png"), vec![]);
+    let server = TestServer::start(HTTPServer::new("127.0.0.1:0")
+        .add_router(router)
+        .enable_compression()
+        .compression_min_size(64)
+        .compressible_types(["text/*", "application/json", "image/svg+xml"])).await;
+    let gzip = [("Accept-Encoding", "gzip")];
+
+    let tiny = server.request("GET", "/tiny.json", &gzip, b"").await;
+    let page = server.request("GET", "/page", &gzip, b"").await;
+    let logo = server.request("GET", "/logo.png", &gzip, b"").await;
+
+    assert_eq!(tiny.header("Content-Encoding"), None);
+    assert_eq!(tiny.text(), "{\"ok\":1}");
+    assert_eq!(page.header("Content-Encoding"), Some("gzip"));
+    assert!(page.body.len() < 2600);
+    assert_eq!(logo.header("Content-Encoding"), None);
+    assert_eq!(logo.body.len(), 4096);
+}
+
+#[tokio::test]
+async fn compression_leaves_streams_and_bodyless_responses_alone() {
+    let router = Router::new("/")
+        .get("/events", |_req: HTTPRequest| {
+            let (sender, receiver) = tokio::sync::mpsc::channel(4);
+            tokio::spawn(async move {
+                let _ = sender.send(SseEvent::data("tick")).await;
+            });
+            HTTPResponse::event_stream(receiver)
+        }, vec![])
+        .delete("/items/1", |_req: HTTPRequest| HTTPResponse::no_content(), vec![])
+        .get("/about", |_req: HTTPRequest| HTTPResponse::ok("").with_html_body("<h1>About Us</h1>"), vec![]);
+    let server = TestServer::start(HTTPServer::new("127.0.0.1:0")
+        .add_router(router)
+        .compression_min_size(0)).await;
+    let gzip = [("Accept-Encoding", "gzip")];
+
+    let events = server.request("GET", "/events", &gzip, b"").await;
+    let deleted = server.request("DELETE", "/items/1", &gzip, b"").await;
+    let head = server.request("HEAD", "/about", &gzip, b"").await;
+
+    assert_eq!(events.header("Content-Encoding"), None);
+    assert!(events.text().contains("data: tick\n\n"));
+    assert_eq!(deleted.status, 204);
+    assert_eq!(deleted.header("Content-Encoding"), None);
+    assert!(deleted.body.is_empty());
+    assert_eq!(head.header("Content-Encoding"), None);
+    assert_eq!(head.header("Content-Length"), Some("17"));
+}
+
+#[tokio::test]
+async fn keep_alive_connections_close_after_max_requests() {
+    let router = Router::new("/").get("/ping", |_req: HTTPRequest| "pong", vec![]);
+    let server = TestServer::start(HTTPServer::new("127.0.0.1:0")
+        .add_router(router)
+        .max_requests_per_connection(3)).await;
+    let mut stream = server.connect().await;
+
+    let mut responses = Vec::new();
+    for _ in 0..3 {
+        stream.write_all(b"GET /ping HTTP/1.1\r\nHost: localhost\r\n\r\n").await.expect("send");
+        responses.push(common::TestResponse::read_from(&mut stream).await);
+    }
+    // A 4th request gets no answer: the server has hung up
+    let _ = stream.write_all(b"GET /ping HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    let mut rest = Vec::new();
+    let _ = stream.read_to_end(&mut rest).await;
+
+    // A limit of 0 acts like 1
+    let single = TestServer::start(HTTPServer::new("127.0.0.1:0")
+        .add_router(Router::new("/").get("/ping", |_req: HTTPRequest| "pong", vec![]))
+        .max_requests_per_connection(0)).await;
+    let mut single_stream = single.connect().await;
+    single_stream.write_all(b"GET /ping HTTP/1.1\r\nHost: localhost\r\n\r\n").await.expect("send");
+    let only = common::TestResponse::read_from(&mut single_stream).await;
+    let mut single_rest = Vec::new();
+    let _ = single_stream.read_to_end(&mut single_rest).await;
+
+    let connection: Vec<_> = responses.iter().map(|res| res.header("Connection")).collect();
+    assert!(responses.iter().all(|res| res.text() == "pong"));
+    assert_eq!(connection, vec![Some("keep-alive"), Some("keep-alive"), Some("close")]);
+    assert!(rest.is_empty());
+    assert_eq!(only.text(), "pong");
+    assert_eq!(only.header("Connection"), Some("close"));
+    assert!(single_rest.is_empty());
+}
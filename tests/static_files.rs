@@ -0,0 +1,160 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use common::TestServer;
+use http_server_from_scratch::responses::{format_http_date, parse_http_date};
+use http_server_from_scratch::routing::Router;
+use http_server_from_scratch::server::HTTPServer;
+
+// A fresh directory per test with one file in it
+fn static_root(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("http-server-static-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&root).expect("create static root");
+    std::fs::write(root.join("hello.txt"), "hello from disk").expect("write static file");
+    root
+}
+
+async fn serve(root: &Path) -> TestServer {
+    let router = Router::new("/").static_dir("/static", root.to_str().unwrap());
+    TestServer::start(HTTPServer::new("127.0.0.1:0").add_router(router)).await
+}
+
+#[tokio::test]
+async fn if_modified_since_matching_last_modified_gets_304() {
+    let root = static_root("ims-match");
+    let server = serve(&root).await;
+
+    let first = server.get("/static/hello.txt").await;
+    let last_modified = first.header("Last-Modified").expect("Last-Modified is set").to_string();
+    let second = server.request("GET", "/static/hello.txt", &[("If-Modified-Since", &last_modified)], b"").await;
+
+    assert_eq!(first.status, 200);
+    assert_eq!(first.text(), "hello from disk");
+    assert_eq!(second.status, 304);
+    assert!(second.body.is_empty());
+    assert_eq!(second.header("Last-Modified"), Some(last_modified.as_str()));
+}
+
+#[tokio::test]
+async fn older_or_unparseable_dates_get_the_full_file() {
+    let root = static_root("ims-stale");
+    let server = serve(&root).await;
+
+    let older = server.request("GET", "/static/hello.txt", &[("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")], b"").await;
+    let garbage = server.request("GET", "/static/hello.txt", &[("If-Modified-Since", "yesterday-ish")], b"").await;
+
+    assert_eq!(older.status, 200);
+    assert_eq!(older.text(), "hello from disk");
+    assert_eq!(garbage.status, 200);
+}
+
+#[tokio::test]
+async fn if_none_match_wins_over_if_modified_since() {
+    let root = static_root("inm-precedence");
+    let server = serve(&root).await;
+
+    let first = server.get("/static/hello.txt").await;
+    let last_modified = first.header("Last-Modified").unwrap().to_string();
+    let res = server.request("GET", "/static/hello.txt", &[
+        ("If-None-Match", "\"some-other-version\""),
+        ("If-Modified-Since", &last_modified),
+    ], b"").await;
+
+    assert_eq!(res.status, 200);
+}
+
+#[test]
+fn http_dates_round_trip_in_all_three_formats() {
+    let expected = UNIX_EPOCH + Duration::from_secs(784_111_777);
+
+    assert_eq!(format_http_date(expected), "Sun, 06 Nov 1994 08:49:37 GMT");
+    assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(expected));
+    assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), Some(expected));
+    assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), Some(expected));
+    assert_eq!(parse_http_date("Sun, 06 Nov 1994 25:49:37 GMT"), None);
+    assert_eq!(parse_http_date("not a date"), None);
+}
+
+// A tiny built SPA: index.html plus one bundled asset
+async fn serve_spa(name: &str) -> TestServer {
+    let dist = static_root(name);
+    std::fs::create_dir_all(dist.join("assets")).expect("create assets dir");
+    std::fs::write(dist.join("index.html"), "<div id=\"root\"></div>").expect("write index");
+    std::fs::write(dist.join("assets").join("app.js"), "render()").expect("write asset");
+
+    let router = Router::new("/").spa("/app", dist.to_str().unwrap());
+    TestServer::start(HTTPServer::new("127.0.0.1:0").add_router(router)).await
+}
+
+#[tokio::test]
+async fn spa_serves_existing_assets() {
+    let server = serve_spa("spa-asset").await;
+
+    let asset = server.get("/app/assets/app.js").await;
+
+    assert_eq!(asset.status, 200);
+    assert_eq!(asset.text(), "render()");
+}
+
+#[tokio::test]
+async fn spa_routes_fall_back_to_index() {
+    let server = serve_spa("spa-fallback").await;
+
+    for path in ["/app", "/app/", "/app/settings", "/app/users/42/edit"] {
+        let res = server.get(path).await;
+        assert_eq!(res.status, 200, "GET {}", path);
+        assert_eq!(res.header("Content-Type"), Some("text/html"), "GET {}", path);
+        assert_eq!(res.text(), "<div id=\"root\"></div>", "GET {}", path);
+    }
+}
+
+#[tokio::test]
+async fn spa_missing_assets_and_traversal_are_not_index() {
+    let server = serve_spa("spa-missing").await;
+
+    let missing = server.get("/app/assets/missing.js").await;
+    let traversal = server.get("/app/%2e%2e/secret").await;
+    let outside = server.get("/elsewhere").await;
+
+    assert_eq!(missing.status, 404);
+    assert_eq!(traversal.status, 403);
+    assert_eq!(outside.status, 404);
+}
+
+// Bigger than the 64 KiB streaming chunk, and not a multiple of it
+fn large_file(root: &Path) -> Vec<u8> {
+    let contents: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    std::fs::write(root.join("large.bin"), &contents).expect("write large file");
+    contents
+}
+
+#[tokio::test]
+async fn large_files_are_streamed_in_full() {
+    let root = static_root("large");
+    let contents = large_file(&root);
+    let server = serve(&root).await;
+
+    let res = server.get("/static/large.bin").await;
+
+    assert_eq!(res.status, 200);
+    assert_eq!(res.header("Content-Length"), Some("200000"));
+    assert_eq!(res.header("Transfer-Encoding"), None);
+    assert!(res.body == contents, "streamed body differs from the file");
+}
+
+#[tokio::test]
+async fn ranges_and_head_work_on_streamed_files() {
+    let root = static_root("large-range");
+    let contents = large_file(&root);
+    let server = serve(&root).await;
+
+    let partial = server.request("GET", "/static/large.bin", &[("Range", "bytes=70000-140009")], b"").await;
+    let head = server.request("HEAD", "/static/large.bin", &[], b"").await;
+
+    assert_eq!(partial.status, 206);
+    assert_eq!(partial.header("Content-Range"), Some("bytes 70000-140009/200000"));
+    assert!(partial.body == contents[70_000..=140_009], "ranged body differs from the file");
+    assert_eq!(head.header("Content-Length"), Some("200000"));
+    assert!(head.body.is_empty());
+}
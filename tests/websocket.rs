@@ -0,0 +1,8 @@
+use http_server_from_scratch::websocket::accept_key;
+
+// The worked example from RFC 6455 section 1.3
+#[test]
+fn accept_key_matches_the_rfc_6455_example() {
+    assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    assert_eq!(accept_key(" dGhlIHNhbXBsZSBub25jZQ== "), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+}